@@ -2,20 +2,55 @@ use bevy::color::palettes::css::{BLUE, PURPLE, RED, YELLOW};
 use bevy::prelude::*;
 use rand::Rng;
 use rand_chacha::ChaCha12Rng;
-use uuid::Uuid;
+use serde::{Deserialize, Serialize};
+use uuid::{Builder, Uuid};
 
-use crate::common::{self, GameConfig};
+use crate::common::{self, GameConfig, Locomotion, Rewards};
 use crate::food;
+use crate::helpers::brain::{Brain, BrainKind};
+use crate::helpers::neat::{Innovations, NeatGenome};
 use crate::helpers::neural_network::NeuralNetwork;
+use crate::spatial::SpatialIndex;
 
-#[derive(Clone, Debug, Copy, PartialEq)]
+#[derive(Clone, Debug, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Action {
     MovingAround,
     GoingForFood,
-    ////Duplicate,
+    SeekMate,
 }
 
-#[derive(Clone, Debug)]
+// Internal state (6) plus what the cell sees in its vision cone (7).
+pub const BRAIN_INPUTS: usize = 13;
+
+// Three action scores, then turn and thrust for steering locomotion.
+pub const BRAIN_OUTPUTS: usize = 5;
+
+// Radians per second at full turn output.
+const MAX_TURN_RATE: f32 = std::f32::consts::PI;
+
+// How many foods in view saturate the food count input.
+const FOODS_IN_VIEW_CAP: f32 = 10.0;
+
+// Cells can mate once they're this many days old and have this much energy.
+const MATURITY_AGE: i32 = 3;
+const MATING_ENERGY: f32 = 50.0;
+
+// How close two cells have to get to mate.
+pub const MATING_RANGE: f32 = 20.0;
+
+// Energy a child costs, split evenly between both parents.
+const MATING_ENERGY_COST: f32 = 50.0;
+
+// Cells with at least this diet attack other cells.
+pub const PREDATOR_DIET: f32 = 0.5;
+
+// How close a predator has to get to bite.
+pub const ATTACK_RANGE: f32 = 15.0;
+
+// Energy per second each point of strength costs to keep up.
+const STRENGTH_UPKEEP: f32 = 0.05;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Genes {
     pub movement_speed: f32,
     pub vision_range: f32,
@@ -25,13 +60,76 @@ pub struct Genes {
 
     pub energy_weight: f32,
     pub random_weight: f32,
+
+    // 0.0 lives off food only, 1.0 off other cells only.
+    pub diet: f32,
+    // Scales the body: harder to hurt and more to eat, but costlier to run.
+    pub size: f32,
+    // Damage dealt when attacking, paid for in upkeep.
+    pub strength: f32,
 }
 
-#[derive(Component, Clone, Debug)]
+impl Genes {
+    // 1.0 for identical genes, 0.0 for genes at opposite ends of every range.
+    pub fn relatedness(&self, other: &Genes) -> f32 {
+        let color = self.color.to_linear();
+        let other_color = other.color.to_linear();
+
+        let differences = [
+            (self.movement_speed - other.movement_speed).abs() / (100.0 - 15.0),
+            (self.vision_range - other.vision_range).abs() / (400.0 - 100.0),
+            (self.vision_angle - other.vision_angle).abs() / (180.0 - 10.0),
+            (self.metabolism - other.metabolism).abs() / (5.0 - 0.5),
+            (self.energy_weight - other.energy_weight).abs() / (1.2 - 0.9),
+            (self.random_weight - other.random_weight).abs() / (1.5 - 0.5),
+            (self.diet - other.diet).abs(),
+            (self.size - other.size).abs() / (2.0 - 0.5),
+            (self.strength - other.strength).abs() / (5.0 - 0.5),
+            (color.red - other_color.red).abs(),
+            (color.green - other_color.green).abs(),
+            (color.blue - other_color.blue).abs(),
+        ];
+
+        let average = differences.iter().sum::<f32>() / differences.len() as f32;
+        (1.0 - average).clamp(0.0, 1.0)
+    }
+}
+
+// What a cell perceives inside its vision cone. Distances are relative to the vision range
+// and angles to half the vision angle, so both stay within -1.0..=1.0.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Senses {
+    pub nearest_food: Option<(f32, f32)>,
+    pub foods_in_view: usize,
+    // Distance, angle, relatedness and diet.
+    pub nearest_cell: Option<(f32, f32, f32, f32)>,
+}
+
+impl Senses {
+    fn inputs(&self) -> [f32; 7] {
+        // Nothing in view reads as "as far away as possible, straight ahead".
+        let (food_distance, food_angle) = self.nearest_food.unwrap_or((1.0, 0.0));
+        let (cell_distance, cell_angle, relatedness, diet) =
+            self.nearest_cell.unwrap_or((1.0, 0.0, 0.0, 0.0));
+
+        [
+            food_distance,
+            food_angle,
+            (self.foods_in_view as f32 / FOODS_IN_VIEW_CAP).min(1.0),
+            cell_distance,
+            cell_angle,
+            relatedness,
+            diet,
+        ]
+    }
+}
+
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Cell {
     pub health: f32,
     pub energy: f32,
     pub age: i32,
+    // Sum of `Rewards` earned over the cell's life.
     pub fitness: f32,
     pub generation: i32,
     pub id: Uuid,
@@ -40,7 +138,15 @@ pub struct Cell {
     pub target_location: Option<Vec2>,
     pub rotation: f32,
     pub action: Action,
-    pub brain: NeuralNetwork,
+    pub turn: f32,
+    pub thrust: f32,
+    // Whether food was in view last tick, finding food is rewarded once per sighting.
+    pub sees_food: bool,
+    pub brain: Brain,
+    // Hidden state of a recurrent brain from the previous tick.
+    pub memory: Vec<ndarray::Array1<f32>>,
+    // Only assigned for NEAT brains, see `Speciation`.
+    pub species: Option<usize>,
     pub genes: Genes,
     pub offsprings_count: i32,
 }
@@ -75,17 +181,25 @@ impl Cell {
             metabolism: seeded_rng.gen_range(0.5..5.0),
             energy_weight: seeded_rng.gen_range(0.9..1.2),
             random_weight: seeded_rng.gen_range(0.5..1.5),
+            // Mostly herbivores to start with; carnivores have to earn their place.
+            diet: seeded_rng.gen_range(0.0..0.6),
+            size: seeded_rng.gen_range(0.75..1.5),
+            strength: seeded_rng.gen_range(0.5..2.5),
         };
 
         return (
             Mesh2d(cell_mesh.clone()),
             MeshMaterial2d(materials.add(Color::from(color))),
-            Transform::default().with_translation(Vec3::new(x, y, 0.0)),
+            Transform::default()
+                .with_translation(Vec3::new(x, y, 0.0))
+                .with_scale(Vec3::splat(genes.size)),
             crate::common::Collider,
             Cell {
                 fitness: 0.0,
-                brain: NeuralNetwork::new(6, 10, 3),
-                id: Uuid::new_v4(),
+                brain: new_brain(seeded_rng, game_config),
+                memory: Vec::new(),
+                species: None,
+                id: new_id(seeded_rng),
                 pos_x: x,
                 pos_y: y,
                 generation: 0,
@@ -95,12 +209,39 @@ impl Cell {
                 target_location: None,
                 rotation: 0.0,
                 action: Action::MovingAround,
+                turn: 0.0,
+                thrust: 0.0,
+                sees_food: false,
                 genes,
                 offsprings_count: 0,
             },
         );
     }
 
+    // Rebuilds the render components for a cell that already exists, e.g. one loaded from a snapshot.
+    pub fn into_bundle(
+        self,
+        cell_mesh: &Handle<Mesh>,
+        materials: &mut Assets<ColorMaterial>,
+    ) -> (
+        bevy::prelude::Mesh2d,
+        bevy::prelude::MeshMaterial2d<ColorMaterial>,
+        bevy::prelude::Transform,
+        crate::common::Collider,
+        Cell,
+    ) {
+        (
+            Mesh2d(cell_mesh.clone()),
+            MeshMaterial2d(materials.add(self.genes.color)),
+            Transform::default()
+                .with_translation(Vec3::new(self.pos_x, self.pos_y, 0.0))
+                .with_rotation(Quat::from_rotation_z(self.rotation))
+                .with_scale(Vec3::splat(self.genes.size)),
+            crate::common::Collider,
+            self,
+        )
+    }
+
     pub fn random_target(&mut self, seeded_rng: &mut ChaCha12Rng, game_config: &GameConfig) {
         let distance = seeded_rng.gen_range(0.0..self.genes.vision_range);
 
@@ -127,12 +268,7 @@ impl Cell {
         self.target_location = Some(clamped_target_position);
     }
 
-    pub fn draw_vision(
-        &self,
-        gizmos: &mut Gizmos,
-        all_cells: &Vec<Cell>,
-        all_foods: &Vec<food::Food>,
-    ) {
+    pub fn draw_vision(&self, gizmos: &mut Gizmos, index: &SpatialIndex) {
         let center = Vec2::new(self.pos_x, self.pos_y);
         let radius = self.genes.vision_range;
         let rotation = self.rotation;
@@ -169,16 +305,19 @@ impl Cell {
             gizmos.circle_2d(location, 1.0, Color::linear_rgb(1.0, 0.0, 0.0));
         }
 
-        for cell in all_cells {
-            let cell_position = Vec2::new(cell.pos_x, cell.pos_y);
-            if self.is_within_vision_cone(cell_position) {
-                gizmos.circle_2d(cell_position, 20.0, self.genes.color);
-            }
-        }
-        for food in all_foods {
-            let food_position = Vec2::new(food.pos_x, food.pos_y);
-            if self.is_within_vision_cone(food_position) {
-                gizmos.circle_2d(food_position, 20.0, self.genes.color);
+        let seen = index
+            .cells
+            .query(center, radius)
+            .map(|(position, _)| position)
+            .chain(
+                index
+                    .foods
+                    .query(center, radius)
+                    .map(|(position, _)| position),
+            );
+        for position in seen {
+            if self.is_within_vision_cone(position) {
+                gizmos.circle_2d(position, 20.0, self.genes.color);
             }
         }
     }
@@ -218,47 +357,90 @@ impl Cell {
         }
     }
 
-    pub fn process_metabolism(&mut self, time: f32) {
+    pub fn steer(&mut self, transform: &mut Transform, game_config: &GameConfig, time: f32) {
+        let mut movement_speed = self.genes.movement_speed * self.thrust;
+        if self.energy < 30.0 {
+            movement_speed *= 0.5;
+        }
+
+        self.rotation += self.turn * MAX_TURN_RATE * time;
+        let direction = Vec2::new(0.0, 1.0).rotate(Vec2::from_angle(self.rotation));
+
+        let nx = (self.pos_x + direction.x * movement_speed * time)
+            .clamp(-game_config.map_width / 2., game_config.map_width / 2.);
+        let ny = (self.pos_y + direction.y * movement_speed * time)
+            .clamp(-game_config.map_height / 2., game_config.map_height / 2.);
+
+        self.pos_x = nx;
+        self.pos_y = ny;
+        transform.translation.x = nx;
+        transform.translation.y = ny;
+        transform.rotation = Quat::from_rotation_z(self.rotation);
+    }
+
+    pub fn process_metabolism(&mut self, game_config: &GameConfig, time: f32) {
         let base_rate = self.genes.metabolism;
 
         let activity_factor = match self.action {
             Action::MovingAround => 1.0,
             Action::GoingForFood => 1.2,
-            //Action::Duplicate => 2.0,
+            Action::SeekMate => 1.5,
         };
 
-        let metabolism_rate = base_rate * activity_factor;
+        let mut metabolism_rate =
+            base_rate * activity_factor * self.genes.size + self.genes.strength * STRENGTH_UPKEEP;
+        if game_config.locomotion == Locomotion::Steering {
+            metabolism_rate += game_config.thrust_energy_cost * self.thrust;
+        }
 
         self.energy = (self.energy - metabolism_rate * time).clamp(0.0, 100.0);
+        self.fitness += game_config.rewards.tick_alive * time;
 
         if self.energy <= 0.0 {
             self.health = (self.health - (metabolism_rate * time)).clamp(0.0, 100.0);
+            self.fitness += game_config.rewards.hunger * time;
         }
     }
 
     pub fn process_brain(
         &mut self,
         rng: &mut ChaCha12Rng,
-        all_cells: &Vec<Cell>,
-        all_foods: &Vec<food::Food>,
+        index: &SpatialIndex,
+        game_config: &GameConfig,
     ) {
         let energy_score = (self.energy.clamp(0.0001, 100.0) / 100.0);
         let low_energy = if self.energy < 30.0 { 1.0 } else { 0.0 };
         let is_healthy = if self.health > 70.0 { 1.0 } else { 0.0 };
         let random_score = self.genes.random_weight * rng.gen_range(0.0..0.1);
+        let senses = self.sense(index);
+        if senses.nearest_food.is_some() && !self.sees_food {
+            self.fitness += game_config.rewards.found_food;
+        }
+        self.sees_food = senses.nearest_food.is_some();
 
-        let inputs = ndarray::array![
+        let inputs: ndarray::Array1<f32> = [
             self.health / 100.0,
             energy_score,
             low_energy,
             is_healthy,
             self.genes.metabolism / 100.0,
             random_score,
-        ];
+        ]
+        .into_iter()
+        .chain(senses.inputs())
+        .collect();
+
+        let outputs = self.brain.feedforward(&inputs, &mut self.memory);
+
+        // Read in 0..1 whatever the output activation, turn is centered so the cell can go
+        // either way.
+        let activation = game_config.output_activation;
+        self.turn = (activation.unit(outputs[3]) * 2.0 - 1.0).clamp(-1.0, 1.0);
+        self.thrust = activation.unit(outputs[4]).clamp(0.0, 1.0);
 
-        let outputs = self.brain.feedforward(&inputs);
         let action_index = outputs
             .iter()
+            .take(3)
             .enumerate()
             .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
             .unwrap();
@@ -266,14 +448,71 @@ impl Cell {
         self.action = match action_index.0 {
             0 => Action::MovingAround,
             1 => Action::GoingForFood,
-            //2 => Action::Duplicate,
+            2 => Action::SeekMate,
             _ => Action::MovingAround,
         };
     }
 
+    pub fn sense(&self, index: &SpatialIndex) -> Senses {
+        let mut senses = Senses::default();
+        let position = Vec2::new(self.pos_x, self.pos_y);
+
+        for (food_position, _) in index.foods.query(position, self.genes.vision_range) {
+            if let Some((distance, angle)) = self.perceive(food_position) {
+                senses.foods_in_view += 1;
+                if senses
+                    .nearest_food
+                    .map_or(true, |(nearest, _)| distance < nearest)
+                {
+                    senses.nearest_food = Some((distance, angle));
+                }
+            }
+        }
+
+        for (cell_position, cell) in index.cells.query(position, self.genes.vision_range) {
+            if cell.id == self.id {
+                continue;
+            }
+            if let Some((distance, angle)) = self.perceive(cell_position) {
+                if senses
+                    .nearest_cell
+                    .map_or(true, |(nearest, _, _, _)| distance < nearest)
+                {
+                    senses.nearest_cell = Some((
+                        distance,
+                        angle,
+                        self.genes.relatedness(&cell.genes),
+                        cell.genes.diet,
+                    ));
+                }
+            }
+        }
+
+        senses
+    }
+
+    // Normalized distance and signed angle to a point, if it's inside the vision cone.
+    fn perceive(&self, target_position: Vec2) -> Option<(f32, f32)> {
+        if !self.is_within_vision_cone(target_position) {
+            return None;
+        }
+
+        let cell_position = Vec2::new(self.pos_x, self.pos_y);
+        let cell_forward = Vec2::new(0.0, 1.0).rotate(Vec2::from_angle(self.rotation));
+        let angle = cell_forward
+            .angle_to(target_position - cell_position)
+            .to_degrees();
+
+        Some((
+            cell_position.distance(target_position) / self.genes.vision_range,
+            (angle / (self.genes.vision_angle / 2.0)).clamp(-1.0, 1.0),
+        ))
+    }
+
     pub fn create_offspring(
         &mut self,
         seeded_rng: &mut ChaCha12Rng,
+        innovations: &mut Innovations,
         game_config: &GameConfig,
         cell_mesh: &Handle<Mesh>,
         materials: &mut Assets<ColorMaterial>,
@@ -286,9 +525,10 @@ impl Cell {
     ) {
         self.energy -= 50.0;
         self.offsprings_count += 1;
+        self.fitness += game_config.rewards.reproduction;
 
-        let mut offspring_network = self.brain.clone();
-        Cell::mutate(&mut offspring_network, game_config);
+        let mut offspring_brain = self.brain.clone();
+        Cell::mutate(&mut offspring_brain, seeded_rng, innovations, game_config);
 
         let genes = Genes {
             movement_speed: mutate_gene(
@@ -334,6 +574,27 @@ impl Cell {
                 0.5,
                 1.5,
             ),
+            diet: mutate_gene(
+                self.genes.diet,
+                seeded_rng,
+                game_config.mutation_rate,
+                0.0,
+                1.0,
+            ),
+            size: mutate_gene(
+                self.genes.size,
+                seeded_rng,
+                game_config.mutation_rate,
+                0.5,
+                2.0,
+            ),
+            strength: mutate_gene(
+                self.genes.strength,
+                seeded_rng,
+                game_config.mutation_rate,
+                0.5,
+                5.0,
+            ),
         };
 
         let x = self.pos_x;
@@ -342,12 +603,16 @@ impl Cell {
         return (
             Mesh2d(cell_mesh.clone()),
             MeshMaterial2d(materials.add(Color::from(genes.color))),
-            Transform::default().with_translation(Vec3::new(x, y, 0.0)),
+            Transform::default()
+                .with_translation(Vec3::new(x, y, 0.0))
+                .with_scale(Vec3::splat(genes.size)),
             crate::common::Collider,
             Cell {
                 fitness: 0.0,
-                brain: offspring_network,
-                id: Uuid::new_v4(),
+                brain: offspring_brain,
+                memory: Vec::new(),
+                species: self.species,
+                id: new_id(seeded_rng),
                 pos_x: x,
                 pos_y: y,
                 health: 100.0,
@@ -356,6 +621,9 @@ impl Cell {
                 target_location: None,
                 rotation: 0.0,
                 action: Action::MovingAround,
+                turn: 0.0,
+                thrust: 0.0,
+                sees_food: false,
                 generation: self.generation + 1,
                 genes,
                 offsprings_count: 0,
@@ -363,6 +631,146 @@ impl Cell {
         );
     }
 
+    pub fn is_mature(&self) -> bool {
+        self.age >= MATURITY_AGE && self.energy >= MATING_ENERGY
+    }
+
+    // This cell has to be looking for a mate; both have to be mature, alike enough and
+    // share a brain kind.
+    pub fn can_mate_with(&self, other: &Cell, game_config: &GameConfig) -> bool {
+        self.id != other.id
+            && self.action == Action::SeekMate
+            && self.is_mature()
+            && other.is_mature()
+            && std::mem::discriminant(&self.brain) == std::mem::discriminant(&other.brain)
+            && self.genes.relatedness(&other.genes) >= game_config.mating_relatedness
+    }
+
+    // Two-parent offspring, see `pay_for_mating` for who pays.
+    pub fn mate(
+        &mut self,
+        mate: &Cell,
+        seeded_rng: &mut ChaCha12Rng,
+        innovations: &mut Innovations,
+        game_config: &GameConfig,
+        cell_mesh: &Handle<Mesh>,
+        materials: &mut Assets<ColorMaterial>,
+    ) -> (
+        bevy::prelude::Mesh2d,
+        bevy::prelude::MeshMaterial2d<ColorMaterial>,
+        bevy::prelude::Transform,
+        crate::common::Collider,
+        Cell,
+    ) {
+        let child = self.child(mate, seeded_rng, innovations, game_config);
+        self.pay_for_mating(&game_config.rewards);
+
+        child.into_bundle(cell_mesh, materials)
+    }
+
+    // A child of this cell and `mate`, placed between them: crossed over brain, blended genes.
+    pub fn child(
+        &self,
+        mate: &Cell,
+        seeded_rng: &mut ChaCha12Rng,
+        innovations: &mut Innovations,
+        game_config: &GameConfig,
+    ) -> Cell {
+        let (fitter, other) = if mate.fitness > self.fitness {
+            (&mate.brain, &self.brain)
+        } else {
+            (&self.brain, &mate.brain)
+        };
+        let mut offspring_brain =
+            Brain::crossover(fitter, other, game_config.crossover, seeded_rng);
+        Cell::mutate(&mut offspring_brain, seeded_rng, innovations, game_config);
+
+        let rate = game_config.mutation_rate;
+        let genes = Genes {
+            movement_speed: blend(
+                self.genes.movement_speed,
+                mate.genes.movement_speed,
+                seeded_rng,
+                rate,
+            )
+            .clamp(15.0, 100.0),
+            vision_range: blend(
+                self.genes.vision_range,
+                mate.genes.vision_range,
+                seeded_rng,
+                rate,
+            )
+            .clamp(100.0, 400.0),
+            vision_angle: blend(
+                self.genes.vision_angle,
+                mate.genes.vision_angle,
+                seeded_rng,
+                rate,
+            )
+            .clamp(10.0, 180.0),
+            metabolism: blend(
+                self.genes.metabolism,
+                mate.genes.metabolism,
+                seeded_rng,
+                rate,
+            )
+            .clamp(0.5, 5.0),
+            color: blend_colors(
+                self.genes.color.to_linear(),
+                mate.genes.color.to_linear(),
+                seeded_rng,
+            ),
+            energy_weight: blend(
+                self.genes.energy_weight,
+                mate.genes.energy_weight,
+                seeded_rng,
+                rate,
+            )
+            .clamp(0.9, 1.2),
+            random_weight: blend(
+                self.genes.random_weight,
+                mate.genes.random_weight,
+                seeded_rng,
+                rate,
+            )
+            .clamp(0.5, 1.5),
+            diet: blend(self.genes.diet, mate.genes.diet, seeded_rng, rate).clamp(0.0, 1.0),
+            size: blend(self.genes.size, mate.genes.size, seeded_rng, rate).clamp(0.5, 2.0),
+            strength: blend(self.genes.strength, mate.genes.strength, seeded_rng, rate)
+                .clamp(0.5, 5.0),
+        };
+
+        Cell {
+            fitness: 0.0,
+            brain: offspring_brain,
+            memory: Vec::new(),
+            species: self.species,
+            id: new_id(seeded_rng),
+            pos_x: (self.pos_x + mate.pos_x) / 2.0,
+            pos_y: (self.pos_y + mate.pos_y) / 2.0,
+            health: 100.0,
+            energy: 50.0,
+            age: 1,
+            target_location: None,
+            rotation: 0.0,
+            action: Action::MovingAround,
+            turn: 0.0,
+            thrust: 0.0,
+            sees_food: false,
+            generation: self.generation.max(mate.generation) + 1,
+            genes,
+            offsprings_count: 0,
+        }
+    }
+
+    // Each parent's half of a child's cost; `mate` charges the cell that started the mating,
+    // the caller charges the other one.
+    pub fn pay_for_mating(&mut self, rewards: &Rewards) {
+        self.energy -= MATING_ENERGY_COST / 2.0;
+        self.offsprings_count += 1;
+        self.fitness += rewards.reproduction;
+    }
+
     pub fn is_within_vision_cone(&self, target_position: Vec2) -> bool {
         let cell_position = Vec2::new(self.pos_x, self.pos_y);
 
@@ -380,36 +788,97 @@ impl Cell {
         angle_to_target.abs() <= self.genes.vision_angle / 2.0
     }
 
-    pub fn eat(&mut self, food: &food::Food) {
-        self.energy = (self.energy + 15.0).clamp(0.0, 100.0);
-        self.health = (self.energy + 15.0).clamp(0.0, 100.0);
+    // With predation on, food is worth less the more carnivorous the cell is.
+    pub fn eat(&mut self, food: &food::Food, game_config: &GameConfig) {
+        let nutrition = if game_config.predation {
+            food.nutrition * (1.0 - self.genes.diet)
+        } else {
+            food.nutrition
+        };
+        self.fitness += game_config.rewards.ate_food;
+        self.energy = (self.energy + nutrition).clamp(0.0, 100.0);
+        self.health = (self.health + nutrition).clamp(0.0, 100.0);
     }
 
-    pub fn mutate(network: &mut NeuralNetwork, game_config: &GameConfig) {
-        let mut rng = rand::thread_rng();
-        for weight in network.weights_input_hidden.iter_mut() {
-            if rng.gen::<f32>() < game_config.mutation_rate {
-                *weight += rng.gen_range(-0.1..0.1);
-            }
-        }
-        for weight in network.weights_hidden_output.iter_mut() {
-            if rng.gen::<f32>() < game_config.mutation_rate {
-                *weight += rng.gen_range(-0.1..0.1);
-            }
-        }
-        for bias in network.biases_hidden.iter_mut() {
-            if rng.gen::<f32>() < game_config.mutation_rate {
-                *bias += rng.gen_range(-0.1..0.1);
-            }
+    pub fn is_predator(&self) -> bool {
+        self.genes.diet >= PREDATOR_DIET
+    }
+
+    // Bites `prey` for a tick, bigger prey takes less damage. Returns whether it died, in
+    // which case its energy and body go to this cell in proportion to diet.
+    pub fn attack(&mut self, prey: &mut Cell, game_config: &GameConfig, time: f32) -> bool {
+        let damage = self.genes.strength * game_config.attack_damage * time / prey.genes.size;
+        prey.health = (prey.health - damage).max(0.0);
+        if prey.health > 0.0 {
+            return false;
         }
-        for bias in network.biases_output.iter_mut() {
-            if rng.gen::<f32>() < game_config.mutation_rate {
-                *bias += rng.gen_range(-0.1..0.1);
+
+        let meat = prey.energy + game_config.carcass_energy * prey.genes.size;
+        prey.energy = 0.0;
+        self.energy = (self.energy + meat * self.genes.diet).clamp(0.0, 100.0);
+        self.fitness += game_config.rewards.kill;
+        true
+    }
+
+    pub fn mutate(
+        brain: &mut Brain,
+        rng: &mut ChaCha12Rng,
+        innovations: &mut Innovations,
+        game_config: &GameConfig,
+    ) {
+        match brain {
+            Brain::Network(network) => {
+                network.mutate_weights(rng, game_config.mutation_rate, 0.1);
+
+                if rng.gen::<f32>() < game_config.structural_mutation_rate {
+                    network.add_hidden_neuron(rng);
+                }
+                if rng.gen::<f32>() < game_config.structural_mutation_rate {
+                    network.remove_hidden_neuron(rng);
+                }
             }
+            Brain::Neat(genome) => genome.mutate(
+                rng,
+                innovations,
+                game_config.mutation_rate,
+                game_config.neat_add_node_rate,
+                game_config.neat_add_connection_rate,
+            ),
         }
     }
 }
 
+fn new_brain(seeded_rng: &mut ChaCha12Rng, game_config: &GameConfig) -> Brain {
+    if game_config.brain_kind == BrainKind::Neat {
+        return Brain::Neat(NeatGenome::new(
+            seeded_rng,
+            BRAIN_INPUTS,
+            BRAIN_OUTPUTS,
+            game_config.hidden_activation,
+            game_config.output_activation,
+        ));
+    }
+
+    let mut sizes = vec![BRAIN_INPUTS];
+    sizes.extend(
+        std::iter::repeat(game_config.hidden_layer_size.max(1)).take(game_config.hidden_layers),
+    );
+    sizes.push(BRAIN_OUTPUTS);
+
+    Brain::Network(NeuralNetwork::new(
+        seeded_rng,
+        &sizes,
+        game_config.hidden_activation,
+        game_config.output_activation,
+        game_config.recurrent,
+    ))
+}
+
+// Ids come from the seeded rng too, so a seed reproduces the whole population.
+fn new_id(seeded_rng: &mut ChaCha12Rng) -> Uuid {
+    Builder::from_random_bytes(seeded_rng.gen()).into_uuid()
+}
+
 pub fn blend_colors(color1: LinearRgba, color2: LinearRgba, rng: &mut ChaCha12Rng) -> Color {
     let r = (color1.red + color2.red) / 2.0 * rng.gen_range(0.9..1.1);
     let g = (color1.green + color2.green) / 2.0 * rng.gen_range(0.9..1.1);
@@ -431,3 +900,335 @@ fn mutate_gene(value: f32, rng: &mut ChaCha12Rng, mutation_rate: f32, min: f32,
     let mutation = rng.gen_range(-mutation_rate..mutation_rate) * value;
     (value + mutation).clamp(min, max)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spatial::CellEntry;
+    use rand::SeedableRng;
+
+    fn cell(rng: &mut ChaCha12Rng, materials: &mut Assets<ColorMaterial>) -> Cell {
+        let mut cell = Cell::new(rng, &common::GAME_CONFIG, &Handle::default(), materials).4;
+        cell.age = MATURITY_AGE;
+        cell.energy = 80.0;
+        cell.action = Action::SeekMate;
+        cell
+    }
+
+    // At the origin facing +y, seeing 200 ahead and 45 degrees to either side.
+    fn looking_up(rng: &mut ChaCha12Rng) -> Cell {
+        let mut cell = cell(rng, &mut Assets::<ColorMaterial>::default());
+        (cell.pos_x, cell.pos_y, cell.rotation) = (0.0, 0.0, 0.0);
+        cell.genes.vision_range = 200.0;
+        cell.genes.vision_angle = 90.0;
+        cell
+    }
+
+    fn index_with_foods(foods: &[Vec2]) -> SpatialIndex {
+        let mut index = SpatialIndex::default();
+        for (n, food) in foods.iter().enumerate() {
+            index.foods.insert(*food, Entity::from_raw(n as u32));
+        }
+        index
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn mating_shares_the_cost_and_blends_genes() {
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        let mut materials = Assets::<ColorMaterial>::default();
+        let mut first = cell(&mut rng, &mut materials);
+        let mut second = first.clone();
+        second.id = new_id(&mut rng);
+        second.generation = 4;
+        second.genes.movement_speed = 100.0;
+        first.genes.movement_speed = 15.0;
+
+        assert!(first.can_mate_with(&second, &common::GAME_CONFIG));
+
+        let child = first
+            .mate(
+                &second,
+                &mut rng,
+                &mut Innovations::default(),
+                &common::GAME_CONFIG,
+                &Handle::default(),
+                &mut materials,
+            )
+            .4;
+        second.pay_for_mating(&common::GAME_CONFIG.rewards);
+
+        assert_eq!(first.energy, 80.0 - MATING_ENERGY_COST / 2.0);
+        assert_eq!(second.energy, first.energy);
+        assert_eq!(first.offsprings_count, 1);
+        assert_eq!(second.offsprings_count, 1);
+        assert_eq!(child.generation, 5);
+        assert_eq!(first.fitness, common::REWARDS.reproduction);
+        assert_eq!(second.fitness, first.fitness);
+        assert!((15.0..=100.0).contains(&child.genes.movement_speed));
+        assert!(child.genes.movement_speed > 40.0 && child.genes.movement_speed < 75.0);
+    }
+
+    #[test]
+    fn only_mature_seekers_mate() {
+        let mut rng = ChaCha12Rng::seed_from_u64(2);
+        let mut materials = Assets::<ColorMaterial>::default();
+        let mut first = cell(&mut rng, &mut materials);
+        let mut second = first.clone();
+        second.id = new_id(&mut rng);
+
+        assert!(!first.can_mate_with(&first.clone(), &common::GAME_CONFIG));
+
+        second.age = 1;
+        assert!(!first.can_mate_with(&second, &common::GAME_CONFIG));
+
+        second.age = MATURITY_AGE;
+        first.action = Action::GoingForFood;
+        assert!(!first.can_mate_with(&second, &common::GAME_CONFIG));
+    }
+
+    #[test]
+    fn rewards_add_up_to_fitness() {
+        let mut rng = ChaCha12Rng::seed_from_u64(3);
+        let mut materials = Assets::<ColorMaterial>::default();
+        let mut cell = cell(&mut rng, &mut materials);
+        let rewards = &common::GAME_CONFIG.rewards;
+
+        cell.process_metabolism(&common::GAME_CONFIG, 2.0);
+        assert_eq!(cell.fitness, 2.0 * rewards.tick_alive);
+
+        cell.eat(
+            &food::Food {
+                pos_x: 0.0,
+                pos_y: 0.0,
+                kind: food::FoodKind::Grass,
+                nutrition: 10.0,
+                patch: None,
+            },
+            &common::GAME_CONFIG,
+        );
+        assert_eq!(cell.fitness, 2.0 * rewards.tick_alive + rewards.ate_food);
+
+        cell.fitness = 0.0;
+        cell.energy = 0.0;
+        cell.process_metabolism(&common::GAME_CONFIG, 1.0);
+        assert_eq!(cell.fitness, rewards.tick_alive + rewards.hunger);
+    }
+
+    #[test]
+    fn diet_only_costs_food_energy_with_predation() {
+        let mut rng = ChaCha12Rng::seed_from_u64(5);
+        let mut materials = Assets::<ColorMaterial>::default();
+        let grass = food::Food {
+            pos_x: 0.0,
+            pos_y: 0.0,
+            kind: food::FoodKind::Grass,
+            nutrition: 10.0,
+            patch: None,
+        };
+        let mut cell = cell(&mut rng, &mut materials);
+        cell.genes.diet = 0.4;
+
+        (cell.energy, cell.health) = (50.0, 80.0);
+        cell.eat(&grass, &common::GAME_CONFIG);
+        assert_eq!(cell.energy, 60.0);
+        assert_eq!(cell.health, 90.0);
+
+        (cell.energy, cell.health) = (50.0, 95.0);
+        let game_config = common::GameConfig {
+            predation: true,
+            ..common::GAME_CONFIG
+        };
+        cell.eat(&grass, &game_config);
+        assert_close(cell.energy, 56.0);
+        assert_eq!(cell.health, 100.0);
+    }
+
+    #[test]
+    fn killing_transfers_energy_by_diet() {
+        let mut rng = ChaCha12Rng::seed_from_u64(4);
+        let mut materials = Assets::<ColorMaterial>::default();
+        let mut predator = cell(&mut rng, &mut materials);
+        let mut prey = cell(&mut rng, &mut materials);
+        let game_config = common::GAME_CONFIG;
+        predator.genes.diet = 0.5;
+        predator.genes.strength = 2.0;
+        predator.energy = 20.0;
+        prey.genes.size = 2.0;
+        prey.energy = 30.0;
+
+        // 2.0 strength * 20.0 damage / 2.0 size takes 20 health a second.
+        assert!(!predator.attack(&mut prey, &game_config, 1.0));
+        assert_eq!(prey.health, 80.0);
+        assert_eq!(predator.energy, 20.0);
+
+        assert!(predator.attack(&mut prey, &game_config, 4.0));
+        assert_eq!(prey.health, 0.0);
+        let meat = 30.0 + game_config.carcass_energy * 2.0;
+        assert_eq!(predator.energy, 20.0 + meat * 0.5);
+        assert_eq!(predator.fitness, game_config.rewards.kill);
+    }
+
+    #[test]
+    fn senses_the_nearest_food_relative_to_vision() {
+        let cell = looking_up(&mut ChaCha12Rng::seed_from_u64(5));
+        let index = index_with_foods(&[Vec2::new(0.0, 100.0), Vec2::new(-10.0, 20.0)]);
+
+        let senses = cell.sense(&index);
+
+        assert_eq!(senses.foods_in_view, 2);
+        let (distance, angle) = senses.nearest_food.unwrap();
+        assert_close(distance, 500f32.sqrt() / 200.0);
+        // Left of the heading is positive, scaled by half the vision angle.
+        assert_close(angle, 0.5f32.atan().to_degrees() / 45.0);
+        let inputs = senses.inputs();
+        assert_close(inputs[0], distance);
+        assert_close(inputs[1], angle);
+        assert_close(inputs[2], 2.0 / FOODS_IN_VIEW_CAP);
+    }
+
+    #[test]
+    fn food_out_of_sight_is_ignored() {
+        let cell = looking_up(&mut ChaCha12Rng::seed_from_u64(6));
+        let index = index_with_foods(&[
+            // Too far, behind and outside the cone.
+            Vec2::new(0.0, 250.0),
+            Vec2::new(0.0, -50.0),
+            Vec2::new(100.0, 10.0),
+        ]);
+
+        let senses = cell.sense(&index);
+
+        assert_eq!(senses.foods_in_view, 0);
+        assert_eq!(senses.nearest_food, None);
+        assert_eq!(senses.inputs()[..3], [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn food_count_saturates_at_the_cap() {
+        let cell = looking_up(&mut ChaCha12Rng::seed_from_u64(7));
+        let ahead = |count: usize| -> Vec<Vec2> {
+            (0..count)
+                .map(|n| Vec2::new(0.0, 10.0 + n as f32 * 5.0))
+                .collect()
+        };
+
+        let few = cell.sense(&index_with_foods(&ahead(5)));
+        let many = cell.sense(&index_with_foods(&ahead(15)));
+
+        assert_close(few.inputs()[2], 0.5);
+        assert_eq!(many.foods_in_view, 15);
+        assert_eq!(many.inputs()[2], 1.0);
+    }
+
+    #[test]
+    fn senses_other_cells_but_not_itself() {
+        let mut rng = ChaCha12Rng::seed_from_u64(8);
+        let cell = looking_up(&mut rng);
+        let mut other_genes = cell.genes.clone();
+        other_genes.diet = 0.8;
+        let mut index = SpatialIndex::default();
+        // Its own entry is skipped by id, wherever the index has it.
+        index.cells.insert(
+            Vec2::new(0.0, 30.0),
+            CellEntry {
+                entity: Entity::from_raw(0),
+                id: cell.id,
+                genes: cell.genes.clone(),
+            },
+        );
+
+        assert_eq!(cell.sense(&index).nearest_cell, None);
+        assert_eq!(cell.sense(&index).inputs()[3..], [1.0, 0.0, 0.0, 0.0]);
+
+        index.cells.insert(
+            Vec2::new(0.0, 50.0),
+            CellEntry {
+                entity: Entity::from_raw(1),
+                id: new_id(&mut rng),
+                genes: other_genes.clone(),
+            },
+        );
+        let senses = cell.sense(&index);
+
+        assert_eq!(
+            senses.nearest_cell,
+            Some((0.25, 0.0, cell.genes.relatedness(&other_genes), 0.8))
+        );
+        assert_eq!(
+            senses.inputs()[3..],
+            [0.25, 0.0, cell.genes.relatedness(&other_genes), 0.8]
+        );
+    }
+
+    #[test]
+    fn steering_turns_then_moves_along_the_heading() {
+        let mut cell = looking_up(&mut ChaCha12Rng::seed_from_u64(9));
+        let mut transform = Transform::default();
+        cell.genes.movement_speed = 50.0;
+
+        // Half turn output for a second turns a quarter circle without moving.
+        (cell.turn, cell.thrust) = (0.5, 0.0);
+        cell.steer(&mut transform, &common::GAME_CONFIG, 1.0);
+        assert_close(cell.rotation, MAX_TURN_RATE / 2.0);
+        assert_eq!((cell.pos_x, cell.pos_y), (0.0, 0.0));
+        assert_eq!(transform.rotation, Quat::from_rotation_z(cell.rotation));
+
+        // Facing -x now, full thrust covers the whole movement speed.
+        (cell.turn, cell.thrust) = (0.0, 1.0);
+        cell.steer(&mut transform, &common::GAME_CONFIG, 1.0);
+        assert_close(cell.pos_x, -50.0);
+        assert_close(cell.pos_y, 0.0);
+        assert_eq!(
+            transform.translation.truncate(),
+            Vec2::new(cell.pos_x, cell.pos_y)
+        );
+    }
+
+    #[test]
+    fn steering_stays_on_the_map() {
+        let mut cell = looking_up(&mut ChaCha12Rng::seed_from_u64(10));
+        let game_config = common::GAME_CONFIG;
+        cell.genes.movement_speed = 100.0;
+        (cell.pos_x, cell.pos_y) = (0.0, game_config.map_height / 2.0 - 10.0);
+        (cell.turn, cell.thrust) = (0.0, 1.0);
+
+        cell.steer(&mut Transform::default(), &game_config, 5.0);
+
+        assert_eq!(cell.pos_y, game_config.map_height / 2.0);
+        assert_close(cell.pos_x, 0.0);
+    }
+
+    #[test]
+    fn thrust_costs_energy_only_when_steering() {
+        let mut rng = ChaCha12Rng::seed_from_u64(11);
+        let base = looking_up(&mut rng);
+        let energy_after = |locomotion: Locomotion, thrust: f32| {
+            let mut cell = base.clone();
+            cell.thrust = thrust;
+            let game_config = GameConfig {
+                locomotion,
+                ..common::GAME_CONFIG
+            };
+            cell.process_metabolism(&game_config, 1.0);
+            cell.energy
+        };
+        let cost = common::GAME_CONFIG.thrust_energy_cost;
+
+        let idle = energy_after(Locomotion::Steering, 0.0);
+        assert_close(idle - energy_after(Locomotion::Steering, 1.0), cost);
+        assert_close(idle - energy_after(Locomotion::Steering, 0.5), cost / 2.0);
+        assert_eq!(
+            energy_after(Locomotion::Targets, 1.0),
+            energy_after(Locomotion::Targets, 0.0)
+        );
+    }
+}
@@ -1,5 +1,12 @@
 use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::generational::Selection;
+use crate::helpers::brain::BrainKind;
+use crate::helpers::neural_network::{Activation, Crossover};
 
 #[derive(Resource, Default)]
 pub struct CellSelected(pub Option<crate::cell::Cell>);
@@ -7,21 +14,59 @@ pub struct CellSelected(pub Option<crate::cell::Cell>);
 #[derive(Resource)]
 pub struct RandomSource(pub ChaCha12Rng);
 
+impl RandomSource {
+    // Generators for a system that runs cells in parallel, drawn in system order so they replay
+    // with the seed.
+    pub fn streams(&mut self) -> RngStreams {
+        RngStreams(self.0.gen())
+    }
+}
+
+// One ChaCha stream per cell rather than per worker thread, so what a cell draws doesn't
+// depend on how the work got split between threads.
+pub struct RngStreams([u8; 32]);
+
+impl RngStreams {
+    pub fn for_cell(&self, id: Uuid) -> ChaCha12Rng {
+        let mut rng = ChaCha12Rng::from_seed(self.0);
+        rng.set_stream(id.as_u64_pair().0);
+        rng
+    }
+}
+
 #[derive(Resource)]
 pub struct FoodMesh(pub Handle<Mesh>);
 
 #[derive(Resource)]
 pub struct CellMesh(pub Handle<Mesh>);
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Rewards {
     pub tick_alive: f32,
     pub found_food: f32,
     pub ate_food: f32,
     pub hunger: f32,
     pub reproduction: f32,
+    pub kill: f32,
 }
 
-#[derive(Resource)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Locomotion {
+    // Cells walk to points picked by `random_target` or to food they see.
+    Targets,
+    // Brain outputs set turn rate and thrust every tick.
+    Steering,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Evolution {
+    // Cells reproduce whenever they can, generations overlap.
+    OpenEnded,
+    // Fixed-size populations live for `generation_days`, then get replaced by their offspring.
+    Generational,
+}
+
+#[derive(Resource, Clone, Serialize, Deserialize)]
 pub struct GameConfig {
     pub map_height: f32,
     pub map_width: f32,
@@ -34,6 +79,52 @@ pub struct GameConfig {
     pub debug_logs: bool,
     pub paused: bool,
     pub dead_cells: i32,
+    pub seed: u64,
+    pub locomotion: Locomotion,
+    pub thrust_energy_cost: f32,
+    // Topology for brains of newly started cells; offspring inherit and mutate their parent's.
+    pub hidden_layers: usize,
+    pub hidden_layer_size: usize,
+    pub hidden_activation: Activation,
+    pub output_activation: Activation,
+    pub structural_mutation_rate: f32,
+    // Gives network brains memory between ticks; NEAT brains stay feed-forward.
+    pub recurrent: bool,
+    // How two parents' network brains are combined into a child's.
+    pub crossover: Crossover,
+    // Lets cells pair up with `SeekMate` on top of splitting once they have enough energy.
+    pub sexual_reproduction: bool,
+    // Lowest gene relatedness two cells can still mate at.
+    pub mating_relatedness: f32,
+    pub brain_kind: BrainKind,
+    pub neat_add_node_rate: f32,
+    pub neat_add_connection_rate: f32,
+    // Genomes further apart than this (NEAT compatibility distance) are different species.
+    pub neat_compatibility_threshold: f32,
+    pub evolution: Evolution,
+    pub generation_days: i32,
+    pub population_size: usize,
+    pub selection: Selection,
+    pub tournament_size: usize,
+    pub elite_count: usize,
+    // Food patches are placed on restart.
+    pub food_patches: usize,
+    pub patch_radius: f32,
+    pub patch_capacity: usize,
+    // Foods a patch grows back per day, before the season's abundance.
+    pub regrowth_rate: f32,
+    // Nutrition food loses per day, before its kind's multiplier.
+    pub food_decay: f32,
+    pub grass_nutrition: f32,
+    pub fruit_nutrition: f32,
+    pub season_length: i32,
+    pub season_amplitude: f32,
+    // Lets cells with a carnivorous diet attack other cells.
+    pub predation: bool,
+    // Health per second a bite takes per point of strength, before the prey's size.
+    pub attack_damage: f32,
+    // Energy a killed cell's body is worth per unit of size, on top of its own energy.
+    pub carcass_energy: f32,
 }
 
 pub const REWARDS: Rewards = Rewards {
@@ -42,6 +133,7 @@ pub const REWARDS: Rewards = Rewards {
     hunger: -5.0,
     ate_food: 15.0,
     reproduction: 50.0,
+    kill: 30.0,
 };
 
 pub const GAME_CONFIG: GameConfig = GameConfig {
@@ -56,6 +148,40 @@ pub const GAME_CONFIG: GameConfig = GameConfig {
     debug_logs: false,
     paused: true,
     dead_cells: 0,
+    seed: 0,
+    locomotion: Locomotion::Targets,
+    thrust_energy_cost: 2.0,
+    hidden_layers: 1,
+    hidden_layer_size: 10,
+    hidden_activation: Activation::Sigmoid,
+    output_activation: Activation::Sigmoid,
+    structural_mutation_rate: 0.05,
+    recurrent: false,
+    crossover: Crossover::Uniform,
+    sexual_reproduction: false,
+    mating_relatedness: 0.6,
+    brain_kind: BrainKind::Network,
+    neat_add_node_rate: 0.03,
+    neat_add_connection_rate: 0.05,
+    neat_compatibility_threshold: 3.0,
+    evolution: Evolution::OpenEnded,
+    generation_days: 10,
+    population_size: 30,
+    selection: Selection::Tournament,
+    tournament_size: 3,
+    elite_count: 5,
+    food_patches: 6,
+    patch_radius: 60.0,
+    patch_capacity: 15,
+    regrowth_rate: 4.0,
+    food_decay: 1.0,
+    grass_nutrition: 10.0,
+    fruit_nutrition: 30.0,
+    season_length: 30,
+    season_amplitude: 0.5,
+    predation: false,
+    attack_damage: 20.0,
+    carcass_energy: 30.0,
 };
 
 #[derive(Component)]
@@ -1,14 +1,82 @@
 use bevy::{color::palettes::css::GREEN, prelude::*};
 use rand::Rng;
 use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Component, Debug, Clone, Copy)]
+use crate::common::{self, GameConfig};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FoodKind {
+    // Common and long lasting, but not very filling.
+    Grass,
+    // Rich, but rots quickly.
+    Fruit,
+}
+
+impl FoodKind {
+    pub fn nutrition(&self, game_config: &GameConfig) -> f32 {
+        match self {
+            FoodKind::Grass => game_config.grass_nutrition,
+            FoodKind::Fruit => game_config.fruit_nutrition,
+        }
+    }
+
+    // Multiplier on `food_decay`.
+    fn decay(&self) -> f32 {
+        match self {
+            FoodKind::Grass => 0.5,
+            FoodKind::Fruit => 2.0,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            FoodKind::Grass => Color::hsl(128., 0.15, 0.61),
+            FoodKind::Fruit => Color::hsl(340., 0.55, 0.6),
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Food {
     pub pos_x: f32,
     pub pos_y: f32,
+    pub kind: FoodKind,
+    // Energy it's still worth; it rots away once this reaches zero.
+    pub nutrition: f32,
+    // The patch it grew in, scattered food has none.
+    pub patch: Option<usize>,
+}
+
+// A spot where one kind of food grows back after being eaten.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Patch {
+    pub center: Vec2,
+    pub kind: FoodKind,
+}
+
+#[derive(Resource, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FoodPatches(pub Vec<Patch>);
+
+impl FoodPatches {
+    pub fn new(seeded_rng: &mut ChaCha12Rng, game_config: &GameConfig) -> FoodPatches {
+        FoodPatches(
+            (0..game_config.food_patches)
+                .map(|_| Patch {
+                    center: random_position(seeded_rng, game_config),
+                    kind: if seeded_rng.gen_bool(0.3) {
+                        FoodKind::Fruit
+                    } else {
+                        FoodKind::Grass
+                    },
+                })
+                .collect(),
+        )
+    }
 }
 
 impl Food {
+    // Grass scattered anywhere on the map.
     pub fn new(
         seeded_rng: &mut ChaCha12Rng,
         game_config: &crate::common::GameConfig,
@@ -21,15 +89,283 @@ impl Food {
         crate::common::Collider,
         Food,
     ) {
-        let x = seeded_rng.gen_range(-game_config.map_width / 2.0..game_config.map_width / 2.0);
-        let y = seeded_rng.gen_range(-game_config.map_height / 2.0..game_config.map_height / 2.0);
+        let position = random_position(seeded_rng, game_config);
+
+        return Food {
+            pos_x: position.x,
+            pos_y: position.y,
+            kind: FoodKind::Grass,
+            nutrition: FoodKind::Grass.nutrition(game_config),
+            patch: None,
+        }
+        .into_bundle(food_mesh, materials);
+    }
 
-        return (
+    // Food of the patch's kind somewhere within `patch_radius` of its center.
+    pub fn in_patch(
+        seeded_rng: &mut ChaCha12Rng,
+        game_config: &crate::common::GameConfig,
+        patches: &FoodPatches,
+        patch: usize,
+        food_mesh: &Handle<Mesh>,
+        materials: &mut Assets<ColorMaterial>,
+    ) -> (
+        bevy::prelude::Mesh2d,
+        bevy::prelude::MeshMaterial2d<ColorMaterial>,
+        bevy::prelude::Transform,
+        crate::common::Collider,
+        Food,
+    ) {
+        let Patch { center, kind } = patches.0[patch];
+        // sqrt spreads food evenly over the disc instead of bunching it at the center.
+        let distance = game_config.patch_radius * seeded_rng.gen_range(0.0f32..1.0).sqrt();
+        let angle = seeded_rng.gen_range(0.0..std::f32::consts::TAU);
+        let position = clamp_to_map(center + Vec2::from_angle(angle) * distance, game_config);
+
+        return Food {
+            pos_x: position.x,
+            pos_y: position.y,
+            kind,
+            nutrition: kind.nutrition(game_config),
+            patch: Some(patch),
+        }
+        .into_bundle(food_mesh, materials);
+    }
+
+    pub fn into_bundle(
+        self,
+        food_mesh: &Handle<Mesh>,
+        materials: &mut Assets<ColorMaterial>,
+    ) -> (
+        bevy::prelude::Mesh2d,
+        bevy::prelude::MeshMaterial2d<ColorMaterial>,
+        bevy::prelude::Transform,
+        crate::common::Collider,
+        Food,
+    ) {
+        (
             Mesh2d(food_mesh.clone()),
-            MeshMaterial2d(materials.add(Color::hsl(128., 0.15, 0.61))),
-            Transform::default().with_translation(Vec3::new(x, y, 0.0)),
+            MeshMaterial2d(materials.add(self.kind.color())),
+            Transform::default().with_translation(Vec3::new(self.pos_x, self.pos_y, 0.0)),
             crate::common::Collider,
-            Food { pos_x: x, pos_y: y },
-        );
+            self,
+        )
+    }
+}
+
+// `count` foods shared out between the patches, or scattered when there are none.
+pub fn spawn_starting_foods(
+    commands: &mut Commands,
+    count: usize,
+    seeded_rng: &mut ChaCha12Rng,
+    game_config: &GameConfig,
+    patches: &FoodPatches,
+    food_mesh: &Handle<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+) {
+    for index in 0..count {
+        if patches.0.is_empty() {
+            commands.spawn(Food::new(seeded_rng, game_config, food_mesh, materials));
+        } else {
+            commands.spawn(Food::in_patch(
+                seeded_rng,
+                game_config,
+                patches,
+                index % patches.0.len(),
+                food_mesh,
+                materials,
+            ));
+        }
+    }
+}
+
+// How much faster than usual patches regrow on `current_day`; seasons cycle every
+// `season_length` days between 1 - `season_amplitude` and 1 + `season_amplitude`.
+pub fn season_abundance(game_config: &GameConfig) -> f32 {
+    let phase = game_config.current_day as f32 / game_config.season_length.max(1) as f32;
+    (1.0 + game_config.season_amplitude * (phase * std::f32::consts::TAU).sin()).max(0.0)
+}
+
+// Food loses nutrition until it rots away, and patches regrow toward `patch_capacity` at
+// `regrowth_rate` foods a day, scaled by the season.
+pub fn food_ecology(
+    mut commands: Commands,
+    mut food_query: Query<(Entity, &mut Food)>,
+    patches: Res<FoodPatches>,
+    time: Res<Time>,
+    game_config: Res<GameConfig>,
+    mut seeded_rng: ResMut<common::RandomSource>,
+    food_mesh: Res<common::FoodMesh>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if game_config.paused {
+        return;
+    }
+    let days = time.delta_secs() / game_config.day_speed;
+
+    let mut growing = vec![0; patches.0.len()];
+    for (entity, mut food) in food_query.iter_mut() {
+        food.nutrition -= food.kind.decay() * game_config.food_decay * days;
+        if food.nutrition <= 0.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if let Some(count) = food.patch.and_then(|patch| growing.get_mut(patch)) {
+            *count += 1;
+        }
+    }
+
+    let chance =
+        (game_config.regrowth_rate * season_abundance(&game_config) * days).clamp(0.0, 1.0);
+    for (patch, count) in growing.into_iter().enumerate() {
+        if count < game_config.patch_capacity && seeded_rng.0.gen_bool(chance as f64) {
+            commands.spawn(Food::in_patch(
+                &mut seeded_rng.0,
+                &game_config,
+                &patches,
+                patch,
+                &food_mesh.0,
+                &mut materials,
+            ));
+        }
+    }
+}
+
+fn random_position(seeded_rng: &mut ChaCha12Rng, game_config: &GameConfig) -> Vec2 {
+    Vec2::new(
+        seeded_rng.gen_range(-game_config.map_width / 2.0..game_config.map_width / 2.0),
+        seeded_rng.gen_range(-game_config.map_height / 2.0..game_config.map_height / 2.0),
+    )
+}
+
+fn clamp_to_map(position: Vec2, game_config: &GameConfig) -> Vec2 {
+    let half = Vec2::new(game_config.map_width, game_config.map_height) / 2.0;
+    position.clamp(-half, half)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+    use rand::SeedableRng;
+    use std::time::Duration;
+
+    fn world(game_config: GameConfig, patches: FoodPatches) -> World {
+        let mut world = World::new();
+        world.insert_resource(common::RandomSource(ChaCha12Rng::seed_from_u64(
+            game_config.seed,
+        )));
+        world.insert_resource(GameConfig {
+            paused: false,
+            ..game_config
+        });
+        world.insert_resource(patches);
+        world.insert_resource(common::FoodMesh(Handle::default()));
+        world.init_resource::<Time>();
+        world.init_resource::<Assets<ColorMaterial>>();
+        world
+    }
+
+    // Runs `food_ecology` once after `seconds` pass.
+    fn tick(world: &mut World, seconds: f32) {
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs_f32(seconds));
+        world.run_system_once(food_ecology).unwrap();
+    }
+
+    fn foods(world: &mut World) -> Vec<Food> {
+        world.query::<&Food>().iter(world).copied().collect()
+    }
+
+    #[test]
+    fn patch_food_grows_near_its_patch() {
+        let mut rng = ChaCha12Rng::seed_from_u64(5);
+        let mut materials = Assets::<ColorMaterial>::default();
+        let game_config = common::GAME_CONFIG;
+        let patches = FoodPatches::new(&mut rng, &game_config);
+        assert_eq!(patches.0.len(), game_config.food_patches);
+
+        for patch in 0..patches.0.len() {
+            let food = Food::in_patch(
+                &mut rng,
+                &game_config,
+                &patches,
+                patch,
+                &Handle::default(),
+                &mut materials,
+            )
+            .4;
+            let center = patches.0[patch].center;
+            assert!(Vec2::new(food.pos_x, food.pos_y).distance(center) <= game_config.patch_radius);
+            assert_eq!(food.kind, patches.0[patch].kind);
+            assert_eq!(food.nutrition, food.kind.nutrition(&game_config));
+            assert_eq!(food.patch, Some(patch));
+        }
+    }
+
+    #[test]
+    fn seasons_cycle() {
+        let at = |current_day| {
+            season_abundance(&GameConfig {
+                current_day,
+                ..common::GAME_CONFIG
+            })
+        };
+        let length = common::GAME_CONFIG.season_length;
+
+        assert!(at(length / 4) > 1.0);
+        assert!(at(3 * length / 4) < 1.0);
+        assert!((at(length) - at(0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn food_decays_and_rots_away() {
+        let game_config = GameConfig {
+            day_speed: 2.0,
+            food_decay: 1.5,
+            regrowth_rate: 0.0,
+            ..common::GAME_CONFIG
+        };
+        let mut world = world(game_config.clone(), FoodPatches::default());
+        let food = |kind, nutrition| Food {
+            pos_x: 0.0,
+            pos_y: 0.0,
+            kind,
+            nutrition,
+            patch: None,
+        };
+        world.spawn(food(FoodKind::Grass, 10.0));
+        world.spawn(food(FoodKind::Fruit, 1.0));
+
+        // A second at 2 seconds a day is half a day.
+        tick(&mut world, 1.0);
+
+        let left = foods(&mut world);
+        assert_eq!(left.len(), 1, "the fruit rotted away");
+        assert_eq!(left[0].kind, FoodKind::Grass);
+        let decayed = FoodKind::Grass.decay() * game_config.food_decay * 0.5;
+        assert!((left[0].nutrition - (10.0 - decayed)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn patches_regrow_up_to_their_capacity() {
+        let game_config = GameConfig {
+            food_decay: 0.0,
+            // Certain to grow every tick while there's room.
+            regrowth_rate: 1000.0,
+            patch_capacity: 3,
+            food_patches: 1,
+            ..common::GAME_CONFIG
+        };
+        let patches = FoodPatches::new(&mut ChaCha12Rng::seed_from_u64(1), &game_config);
+        let mut world = world(game_config, patches);
+
+        for ticks in 1..=10 {
+            tick(&mut world, 1.0 / 60.0);
+            let foods = foods(&mut world);
+            assert_eq!(foods.len(), ticks.min(3));
+            assert!(foods.iter().all(|food| food.patch == Some(0)));
+        }
     }
 }
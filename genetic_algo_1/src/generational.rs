@@ -0,0 +1,316 @@
+use bevy::prelude::*;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::cell::{Action, Cell};
+use crate::common::{self, Evolution, GameConfig};
+use crate::food;
+use crate::helpers::neat::Innovations;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Selection {
+    // The fittest of `tournament_size` random cells.
+    Tournament,
+    // Odds proportional to fitness.
+    Roulette,
+    // Odds proportional to position in the ranking, so outliers don't take over.
+    Rank,
+    // The `elite_count` fittest survive unchanged and parent the rest.
+    Elitism,
+}
+
+impl Selection {
+    pub const ALL: [Selection; 4] = [
+        Selection::Tournament,
+        Selection::Roulette,
+        Selection::Rank,
+        Selection::Elitism,
+    ];
+
+    // Picks a parent from `ranked`, which is sorted fittest first.
+    pub fn pick<'a>(
+        &self,
+        ranked: &'a [Cell],
+        game_config: &GameConfig,
+        rng: &mut ChaCha12Rng,
+    ) -> &'a Cell {
+        let index = match self {
+            Selection::Tournament => (0..game_config.tournament_size.max(1))
+                .map(|_| rng.gen_range(0..ranked.len()))
+                .min()
+                .expect("tournaments have at least one entrant"),
+            Selection::Roulette => {
+                // Shifted so negative fitness works and the least fit still has a small chance.
+                let least = ranked.last().map_or(0.0, |cell| cell.fitness);
+                let weights = ranked.iter().map(|cell| cell.fitness - least + 1.0);
+                WeightedIndex::new(weights)
+                    .expect("weights are positive")
+                    .sample(rng)
+            }
+            Selection::Rank => WeightedIndex::new((1..=ranked.len()).rev())
+                .expect("weights are positive")
+                .sample(rng),
+            Selection::Elitism => rng.gen_range(0..game_config.elite_count.clamp(1, ranked.len())),
+        };
+
+        &ranked[index]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GenerationStats {
+    pub generation: i32,
+    pub days: i32,
+    pub population: usize,
+    pub survivors: usize,
+    pub best_fitness: f32,
+    pub average_fitness: f32,
+    pub worst_fitness: f32,
+}
+
+impl GenerationStats {
+    pub const CSV_HEADER: &'static str =
+        "generation,days,population,survivors,best_fitness,average_fitness,worst_fitness";
+
+    // `ranked` is every cell of the generation, dead or alive, fittest first.
+    fn collect(generation: i32, days: i32, ranked: &[Cell], survivors: usize) -> GenerationStats {
+        GenerationStats {
+            generation,
+            days,
+            population: ranked.len(),
+            survivors,
+            best_fitness: ranked.first().map_or(0.0, |cell| cell.fitness),
+            average_fitness: ranked.iter().map(|cell| cell.fitness).sum::<f32>()
+                / ranked.len().max(1) as f32,
+            worst_fitness: ranked.last().map_or(0.0, |cell| cell.fitness),
+        }
+    }
+
+    pub fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{:.3},{:.3},{:.3}",
+            self.generation,
+            self.days,
+            self.population,
+            self.survivors,
+            self.best_fitness,
+            self.average_fitness,
+            self.worst_fitness
+        )
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Generations {
+    pub current: i32,
+    pub started_day: i32,
+    // Cells that died during the current generation, ranked along with the survivors.
+    pub dead: Vec<Cell>,
+    pub history: Vec<GenerationStats>,
+}
+
+// Ends the generation after `generation_days`, or early when everyone died, and replaces the
+// population with the next one. Food is reset so every generation starts out the same.
+pub fn generation_cycle(
+    mut commands: Commands,
+    mut generations: ResMut<Generations>,
+    game_config: Res<GameConfig>,
+    mut seeded_rng: ResMut<common::RandomSource>,
+    mut innovations: ResMut<Innovations>,
+    patches: Res<food::FoodPatches>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    cell_mesh: Res<common::CellMesh>,
+    food_mesh: Res<common::FoodMesh>,
+    cell_query: Query<(Entity, &Cell)>,
+    food_query: Query<Entity, With<food::Food>>,
+) {
+    if game_config.paused || game_config.evolution != Evolution::Generational {
+        return;
+    }
+    let survivors = cell_query.iter().count();
+    let days = game_config.current_day - generations.started_day;
+    if days < game_config.generation_days && survivors > 0 {
+        return;
+    }
+
+    let mut ranked: Vec<Cell> = cell_query.iter().map(|(_, cell)| cell.clone()).collect();
+    ranked.append(&mut generations.dead);
+    if ranked.is_empty() {
+        return;
+    }
+    ranked.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+
+    let stats = GenerationStats::collect(generations.current, days, &ranked, survivors);
+    generations.history.push(stats);
+    generations.current += 1;
+    generations.started_day = game_config.current_day;
+
+    for (entity, _) in cell_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    for entity in food_query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    for cell in next_generation(&ranked, &game_config, &mut seeded_rng.0, &mut innovations) {
+        commands
+            .spawn(cell.into_bundle(&cell_mesh.0, &mut materials))
+            .observe(crate::select_cell);
+    }
+    food::spawn_starting_foods(
+        &mut commands,
+        crate::STARTING_FOODS,
+        &mut seeded_rng.0,
+        &game_config,
+        &patches,
+        &food_mesh.0,
+        &mut materials,
+    );
+}
+
+// `population_size` cells bred from `ranked` (fittest first) through the configured selection,
+// crossover and mutation.
+pub fn next_generation(
+    ranked: &[Cell],
+    game_config: &GameConfig,
+    rng: &mut ChaCha12Rng,
+    innovations: &mut Innovations,
+) -> Vec<Cell> {
+    let size = game_config.population_size.max(1);
+    let mut population = Vec::with_capacity(size);
+
+    if game_config.selection == Selection::Elitism {
+        for elite in ranked.iter().take(game_config.elite_count.min(size)) {
+            let mut elite = elite.clone();
+            respawn(&mut elite, rng, game_config);
+            population.push(elite);
+        }
+    }
+
+    while population.len() < size {
+        let first = game_config.selection.pick(ranked, game_config, rng);
+        let second = game_config.selection.pick(ranked, game_config, rng);
+        let mut child = first.child(second, rng, innovations, game_config);
+        respawn(&mut child, rng, game_config);
+        population.push(child);
+    }
+
+    population
+}
+
+// Fresh state at a random spot, keeping genes, brain and lineage.
+fn respawn(cell: &mut Cell, rng: &mut ChaCha12Rng, game_config: &GameConfig) {
+    cell.pos_x = rng.gen_range(-game_config.map_width / 2.0..game_config.map_width / 2.0);
+    cell.pos_y = rng.gen_range(-game_config.map_height / 2.0..game_config.map_height / 2.0);
+    cell.rotation = 0.0;
+    cell.health = 100.0;
+    cell.energy = 50.0;
+    cell.age = 1;
+    cell.fitness = 0.0;
+    cell.offsprings_count = 0;
+    cell.target_location = None;
+    cell.action = Action::MovingAround;
+    cell.turn = 0.0;
+    cell.thrust = 0.0;
+    cell.sees_food = false;
+    cell.memory.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn ranked(rng: &mut ChaCha12Rng, fitness: &[f32]) -> Vec<Cell> {
+        let mut materials = Assets::<ColorMaterial>::default();
+        fitness
+            .iter()
+            .map(|&fitness| {
+                let mut cell = Cell::new(
+                    rng,
+                    &common::GAME_CONFIG,
+                    &Handle::default(),
+                    &mut materials,
+                )
+                .4;
+                cell.fitness = fitness;
+                cell
+            })
+            .collect()
+    }
+
+    fn picks(game_config: &GameConfig, ranked: &[Cell], rng: &mut ChaCha12Rng) -> Vec<usize> {
+        let mut counts = vec![0; ranked.len()];
+        for _ in 0..2000 {
+            let picked = game_config.selection.pick(ranked, game_config, rng);
+            counts[ranked.iter().position(|cell| cell.id == picked.id).unwrap()] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn selection_favours_fitter_cells() {
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        let ranked = ranked(&mut rng, &[40.0, 20.0, 10.0, -5.0]);
+
+        for selection in [Selection::Tournament, Selection::Roulette, Selection::Rank] {
+            let game_config = GameConfig {
+                selection,
+                ..common::GAME_CONFIG
+            };
+            let counts = picks(&game_config, &ranked, &mut rng);
+            assert!(counts[0] > counts[3], "{:?} {:?}", selection, counts);
+            assert!(
+                counts.iter().all(|&count| count > 0),
+                "{:?} {:?}",
+                selection,
+                counts
+            );
+        }
+    }
+
+    #[test]
+    fn elitism_keeps_the_best_and_breeds_from_them() {
+        let mut rng = ChaCha12Rng::seed_from_u64(2);
+        let ranked = ranked(&mut rng, &[9.0, 8.0, 7.0, 6.0, 5.0, 4.0, 3.0]);
+        let game_config = GameConfig {
+            selection: Selection::Elitism,
+            elite_count: 2,
+            population_size: 6,
+            ..common::GAME_CONFIG
+        };
+
+        let counts = picks(&game_config, &ranked, &mut rng);
+        assert!(counts[..2].iter().all(|&count| count > 0));
+        assert!(counts[2..].iter().all(|&count| count == 0));
+
+        let population =
+            next_generation(&ranked, &game_config, &mut rng, &mut Innovations::default());
+        assert_eq!(population.len(), 6);
+        assert_eq!(population[0].genes, ranked[0].genes);
+        assert_eq!(population[1].genes, ranked[1].genes);
+        assert!(population
+            .iter()
+            .all(|cell| cell.fitness == 0.0 && cell.age == 1));
+        assert!(population[2..].iter().all(|cell| cell.generation == 1));
+    }
+
+    #[test]
+    fn every_selection_fills_the_population() {
+        let mut rng = ChaCha12Rng::seed_from_u64(3);
+        let ranked = ranked(&mut rng, &[3.0, 3.0, 1.0]);
+
+        for selection in Selection::ALL {
+            let game_config = GameConfig {
+                selection,
+                population_size: 10,
+                ..common::GAME_CONFIG
+            };
+            let population =
+                next_generation(&ranked, &game_config, &mut rng, &mut Innovations::default());
+            assert_eq!(population.len(), 10);
+        }
+    }
+}
@@ -0,0 +1,348 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use bevy::{ecs::system::RunSystemOnce, prelude::*};
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use crate::cell;
+use crate::common;
+use crate::food;
+use crate::generational::{GenerationStats, Generations};
+use crate::helpers::neat::{Innovations, Speciation};
+use crate::spatial::SpatialIndex;
+
+pub const STATS_FILE: &str = "stats.csv";
+pub const GENOMES_FILE: &str = "best_genomes.json";
+pub const GENERATIONS_FILE: &str = "generations.csv";
+const BEST_GENOMES: usize = 10;
+const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// World sizes `benchmark` times, as (cells, foods).
+const BENCHMARK_SIZES: [(usize, usize); 3] = [(1_000, 5_000), (5_000, 25_000), (10_000, 50_000)];
+const BENCHMARK_TICKS: u32 = 60;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayStats {
+    pub day: i32,
+    pub cells: usize,
+    pub dead_cells: i32,
+    pub foods: usize,
+    pub highest_generation: i32,
+    pub average_energy: f32,
+    pub average_fitness: f32,
+    pub best_fitness: f32,
+    pub average_age: f32,
+    pub average_speed: f32,
+    pub average_vision_range: f32,
+    pub average_metabolism: f32,
+    pub average_hidden_neurons: f32,
+    pub species: usize,
+    pub season_abundance: f32,
+    pub predators: usize,
+    pub average_diet: f32,
+    pub average_size: f32,
+    pub average_strength: f32,
+}
+
+impl DayStats {
+    const CSV_HEADER: &'static str = "day,cells,dead_cells,foods,highest_generation,average_energy,average_fitness,best_fitness,average_age,average_speed,average_vision_range,average_metabolism,average_hidden_neurons,species,season_abundance,predators,average_diet,average_size,average_strength";
+
+    fn collect(world: &mut World) -> DayStats {
+        let game_config = world.resource::<common::GameConfig>();
+        let (day, dead_cells) = (game_config.current_day, game_config.dead_cells);
+        let season_abundance = food::season_abundance(game_config);
+        let cells: Vec<cell::Cell> = world.query::<&cell::Cell>().iter(world).cloned().collect();
+        let foods = world.query::<&food::Food>().iter(world).count();
+        let species = world.resource::<Speciation>().species.len();
+
+        let average = |value: fn(&cell::Cell) -> f32| {
+            if cells.is_empty() {
+                0.0
+            } else {
+                cells.iter().map(value).sum::<f32>() / cells.len() as f32
+            }
+        };
+
+        DayStats {
+            day,
+            cells: cells.len(),
+            dead_cells,
+            foods,
+            highest_generation: cells.iter().map(|cell| cell.generation).max().unwrap_or(0),
+            average_energy: average(|cell| cell.energy),
+            average_fitness: average(|cell| cell.fitness),
+            best_fitness: cells.iter().map(|cell| cell.fitness).fold(0.0, f32::max),
+            average_age: average(|cell| cell.age as f32),
+            average_speed: average(|cell| cell.genes.movement_speed),
+            average_vision_range: average(|cell| cell.genes.vision_range),
+            average_metabolism: average(|cell| cell.genes.metabolism),
+            average_hidden_neurons: average(|cell| cell.brain.hidden_neurons() as f32),
+            species,
+            season_abundance,
+            predators: cells.iter().filter(|cell| cell.is_predator()).count(),
+            average_diet: average(|cell| cell.genes.diet),
+            average_size: average(|cell| cell.genes.size),
+            average_strength: average(|cell| cell.genes.strength),
+        }
+    }
+
+    fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{},{:.3},{},{:.3},{:.3},{:.3}",
+            self.day,
+            self.cells,
+            self.dead_cells,
+            self.foods,
+            self.highest_generation,
+            self.average_energy,
+            self.average_fitness,
+            self.best_fitness,
+            self.average_age,
+            self.average_speed,
+            self.average_vision_range,
+            self.average_metabolism,
+            self.average_hidden_neurons,
+            self.species,
+            self.season_abundance,
+            self.predators,
+            self.average_diet,
+            self.average_size,
+            self.average_strength
+        )
+    }
+}
+
+// The simulation without rendering, input or a window; `step` drives it instead of the 60 Hz clock.
+pub fn build_app(game_config: common::GameConfig) -> App {
+    let mut app = App::new();
+
+    app.insert_resource(common::RandomSource(ChaCha12Rng::seed_from_u64(
+        game_config.seed,
+    )))
+    .insert_resource(common::DayNightCycleTimer(Timer::from_seconds(
+        game_config.day_speed,
+        TimerMode::Repeating,
+    )))
+    .insert_resource(game_config)
+    .insert_resource(common::CellSelected(None))
+    .init_resource::<Innovations>()
+    .init_resource::<Speciation>()
+    .init_resource::<Generations>()
+    .init_resource::<SpatialIndex>()
+    .init_resource::<food::FoodPatches>()
+    .insert_resource(common::CellMesh(Handle::default()))
+    .insert_resource(common::FoodMesh(Handle::default()))
+    .init_resource::<Time>()
+    .init_resource::<Assets<ColorMaterial>>()
+    .add_plugins((TaskPoolPlugin::default(), crate::simulation_plugin));
+
+    app.world_mut().run_system_once(crate::start).unwrap();
+    app
+}
+
+pub fn step(world: &mut World) {
+    world.resource_mut::<Time>().advance_by(TICK);
+    world.run_schedule(FixedUpdate);
+}
+
+// Times ticks of ever bigger worlds. The map grows with the population so density, and with it
+// the work per cell, stays about the same; ticks should scale linearly.
+pub fn benchmark(game_config: common::GameConfig) {
+    println!(
+        "Timing {} ticks per size with seed {}",
+        BENCHMARK_TICKS, game_config.seed
+    );
+
+    for (cells, foods) in BENCHMARK_SIZES {
+        let side = 200.0 * (cells as f32).sqrt();
+        let mut app = build_app(common::GameConfig {
+            map_width: side,
+            map_height: side,
+            ..game_config.clone()
+        });
+        let world = app.world_mut();
+        populate(world, cells, foods);
+
+        let started = Instant::now();
+        for _ in 0..BENCHMARK_TICKS {
+            step(world);
+        }
+        let per_tick = started.elapsed() / BENCHMARK_TICKS;
+
+        let index = world.resource::<SpatialIndex>();
+        println!(
+            "{:>6} cells, {:>6} foods: {:.2} ms per tick",
+            index.cells.len(),
+            index.foods.len(),
+            per_tick.as_secs_f32() * 1000.0
+        );
+    }
+}
+
+// Tops a freshly started world up to `cells` cells and `foods` foods.
+fn populate(world: &mut World, cells: usize, foods: usize) {
+    let game_config = world.resource::<common::GameConfig>().clone();
+    let existing_cells = world.query::<&cell::Cell>().iter(world).count();
+    let existing_foods = world.query::<&food::Food>().iter(world).count();
+
+    world.resource_scope(|world, mut materials: Mut<Assets<ColorMaterial>>| {
+        world.resource_scope(|world, mut seeded_rng: Mut<common::RandomSource>| {
+            let new_cells: Vec<_> = (existing_cells..cells)
+                .map(|_| {
+                    cell::Cell::new(
+                        &mut seeded_rng.0,
+                        &game_config,
+                        &Handle::default(),
+                        &mut materials,
+                    )
+                })
+                .collect();
+            let new_foods: Vec<_> = (existing_foods..foods)
+                .map(|_| {
+                    food::Food::new(
+                        &mut seeded_rng.0,
+                        &game_config,
+                        &Handle::default(),
+                        &mut materials,
+                    )
+                })
+                .collect();
+            world.spawn_batch(new_cells);
+            world.spawn_batch(new_foods);
+        });
+    });
+}
+
+pub fn run(game_config: common::GameConfig, days: i32, out_dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(out_dir)?;
+    let mut stats_file = File::create(out_dir.join(STATS_FILE))?;
+    writeln!(stats_file, "{}", DayStats::CSV_HEADER)?;
+    let mut generations_file = File::create(out_dir.join(GENERATIONS_FILE))?;
+    writeln!(generations_file, "{}", GenerationStats::CSV_HEADER)?;
+    let mut generations_written = 0;
+
+    println!(
+        "Running {} days headless with seed {}, writing to {}",
+        days,
+        game_config.seed,
+        out_dir.display()
+    );
+
+    let started = Instant::now();
+    let mut app = build_app(game_config);
+    let world = app.world_mut();
+    let last_day = world.resource::<common::GameConfig>().current_day + days;
+    let mut day = world.resource::<common::GameConfig>().current_day;
+
+    while day < last_day {
+        step(world);
+
+        let history = &world.resource::<Generations>().history;
+        for stats in &history[generations_written..] {
+            writeln!(generations_file, "{}", stats.csv_row())?;
+            println!(
+                "Generation {}: best fitness {:.1}, average {:.1}, {} of {} survived",
+                stats.generation,
+                stats.best_fitness,
+                stats.average_fitness,
+                stats.survivors,
+                stats.population
+            );
+        }
+        generations_written = history.len();
+
+        let current_day = world.resource::<common::GameConfig>().current_day;
+        if current_day == day {
+            continue;
+        }
+        day = current_day;
+
+        let stats = DayStats::collect(world);
+        writeln!(stats_file, "{}", stats.csv_row())?;
+        println!(
+            "Day {}: {} cells, {} foods, generation {}",
+            stats.day, stats.cells, stats.foods, stats.highest_generation
+        );
+
+        if stats.cells == 0 {
+            println!("Population died out on day {}", stats.day);
+            break;
+        }
+    }
+
+    let best = best_genomes(world, BEST_GENOMES);
+    fs::write(
+        out_dir.join(GENOMES_FILE),
+        serde_json::to_string_pretty(&best)?,
+    )?;
+
+    println!(
+        "Finished in {:.1}s, saved {} genomes",
+        started.elapsed().as_secs_f32(),
+        best.len()
+    );
+
+    Ok(())
+}
+
+// Ranked by fitness, then by offspring and age.
+pub fn best_genomes(world: &mut World, count: usize) -> Vec<cell::Cell> {
+    let mut cells: Vec<cell::Cell> = world.query::<&cell::Cell>().iter(world).cloned().collect();
+
+    cells.sort_by(|a, b| {
+        b.fitness
+            .total_cmp(&a.fitness)
+            .then(b.offsprings_count.cmp(&a.offsprings_count))
+            .then(b.age.cmp(&a.age))
+    });
+    cells.truncate(count);
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_runs_write_stats_and_genomes() {
+        let out_dir = std::env::temp_dir().join(format!("headless-run-{}", std::process::id()));
+        let game_config = common::GameConfig {
+            seed: 4,
+            day_speed: 0.1,
+            ..common::GAME_CONFIG
+        };
+
+        run(game_config, 5, &out_dir).unwrap();
+
+        let stats = fs::read_to_string(out_dir.join(STATS_FILE)).unwrap();
+        let mut lines = stats.lines();
+        assert_eq!(lines.next(), Some(DayStats::CSV_HEADER));
+        let days: Vec<i32> = lines
+            .map(|row| {
+                assert_eq!(
+                    row.split(',').count(),
+                    DayStats::CSV_HEADER.split(',').count()
+                );
+                row.split(',').next().unwrap().parse().unwrap()
+            })
+            .collect();
+        assert_eq!(days, vec![2, 3, 4, 5, 6]);
+
+        let generations = fs::read_to_string(out_dir.join(GENERATIONS_FILE)).unwrap();
+        assert_eq!(
+            generations.lines().next(),
+            Some(GenerationStats::CSV_HEADER)
+        );
+
+        let best: Vec<cell::Cell> =
+            serde_json::from_str(&fs::read_to_string(out_dir.join(GENOMES_FILE)).unwrap()).unwrap();
+        assert!(!best.is_empty() && best.len() <= BEST_GENOMES);
+        assert!(best
+            .windows(2)
+            .all(|pair| pair[0].fitness >= pair[1].fitness));
+
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
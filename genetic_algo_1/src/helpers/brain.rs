@@ -0,0 +1,69 @@
+use ndarray::Array1;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::neat::NeatGenome;
+use crate::helpers::neural_network::{Crossover, NeuralNetwork};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BrainKind {
+    Network,
+    Neat,
+}
+
+// Either brain answers the same inputs with the same outputs layout, so cells don't care which
+// one they have.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Brain {
+    Network(NeuralNetwork),
+    Neat(NeatGenome),
+}
+
+impl Brain {
+    // `memory` carries a recurrent network's hidden state between ticks; NEAT genomes are
+    // feed-forward only and leave it untouched.
+    pub fn feedforward(&self, inputs: &Array1<f32>, memory: &mut Vec<Array1<f32>>) -> Array1<f32> {
+        match self {
+            Brain::Network(network) => network.feedforward(inputs, memory),
+            Brain::Neat(genome) => genome.feedforward(inputs),
+        }
+    }
+
+    // NEAT lines genes up by innovation number and ignores `strategy`. Brains of different
+    // kinds can't be combined, the child gets a copy of `fitter`'s.
+    pub fn crossover(
+        fitter: &Brain,
+        other: &Brain,
+        strategy: Crossover,
+        rng: &mut ChaCha12Rng,
+    ) -> Brain {
+        match (fitter, other) {
+            (Brain::Network(a), Brain::Network(b)) => {
+                Brain::Network(NeuralNetwork::crossover(a, b, strategy, rng))
+            }
+            (Brain::Neat(a), Brain::Neat(b)) => Brain::Neat(NeatGenome::crossover(a, b, rng)),
+            _ => fitter.clone(),
+        }
+    }
+
+    pub fn hidden_neurons(&self) -> usize {
+        match self {
+            Brain::Network(network) => network.hidden_neurons(),
+            Brain::Neat(genome) => genome.hidden_neurons(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Brain::Network(network) if network.is_recurrent() => {
+                format!("Recurrent network {:?}", network.sizes())
+            }
+            Brain::Network(network) => format!("Network {:?}", network.sizes()),
+            Brain::Neat(genome) => format!(
+                "NEAT, {} hidden nodes, {} connections",
+                genome.hidden_neurons(),
+                genome.enabled_connections()
+            ),
+        }
+    }
+}
@@ -1,3 +1,5 @@
 pub mod animation;
+pub mod brain;
 pub mod camera;
+pub mod neat;
 pub mod neural_network;
@@ -0,0 +1,680 @@
+use std::collections::HashMap;
+
+use bevy::prelude::Resource;
+use ndarray::Array1;
+use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::neural_network::Activation;
+
+// Ids below these are reserved for the genes every genome starts with (inputs, outputs and
+// the input -> output connections), so those match across genomes without a tracker.
+const FIRST_HIDDEN_NODE: usize = 1 << 16;
+const FIRST_INNOVATION: usize = 1 << 16;
+
+const EXCESS_COEFFICIENT: f32 = 1.0;
+const DISJOINT_COEFFICIENT: f32 = 1.0;
+const WEIGHT_COEFFICIENT: f32 = 0.4;
+// Small genomes compare raw gene counts instead of normalizing them.
+const NORMALIZE_ABOVE_GENES: usize = 20;
+const ADD_CONNECTION_ATTEMPTS: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum NodeKind {
+    Input,
+    Hidden,
+    Output,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeGene {
+    pub id: usize,
+    pub kind: NodeKind,
+    pub bias: f32,
+    pub activation: Activation,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionGene {
+    pub innovation: usize,
+    pub from: usize,
+    pub to: usize,
+    pub weight: f32,
+    pub enabled: bool,
+}
+
+// Hands out the same innovation number (and split node id) to the same structural change,
+// whichever genome makes it first. This is the "historical marking" crossover lines genes up by.
+#[derive(Resource, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Innovations {
+    #[serde(with = "as_pairs")]
+    connections: HashMap<(usize, usize), usize>,
+    #[serde(with = "as_pairs")]
+    splits: HashMap<usize, usize>,
+    next_innovation: usize,
+    next_node: usize,
+}
+
+impl Innovations {
+    fn connection(&mut self, from: usize, to: usize) -> usize {
+        let next = &mut self.next_innovation;
+        *self.connections.entry((from, to)).or_insert_with(|| {
+            *next += 1;
+            FIRST_INNOVATION + *next - 1
+        })
+    }
+
+    fn split_node(&mut self, innovation: usize) -> usize {
+        let next = &mut self.next_node;
+        *self.splits.entry(innovation).or_insert_with(|| {
+            *next += 1;
+            FIRST_HIDDEN_NODE + *next - 1
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NeatGenome {
+    pub inputs: usize,
+    pub outputs: usize,
+    pub hidden_activation: Activation,
+    pub nodes: Vec<NodeGene>,
+    // Kept sorted by innovation number.
+    pub connections: Vec<ConnectionGene>,
+}
+
+impl NeatGenome {
+    // Starts fully connected from inputs to outputs, with no hidden nodes.
+    pub fn new(
+        rng: &mut ChaCha12Rng,
+        inputs: usize,
+        outputs: usize,
+        hidden_activation: Activation,
+        output_activation: Activation,
+    ) -> NeatGenome {
+        let mut nodes = Vec::new();
+        for id in 0..inputs {
+            nodes.push(NodeGene {
+                id,
+                kind: NodeKind::Input,
+                bias: 0.0,
+                activation: Activation::Linear,
+            });
+        }
+        for id in inputs..inputs + outputs {
+            nodes.push(NodeGene {
+                id,
+                kind: NodeKind::Output,
+                bias: rng.gen_range(-1.0..1.0),
+                activation: output_activation,
+            });
+        }
+
+        let mut connections = Vec::new();
+        for from in 0..inputs {
+            for output in 0..outputs {
+                connections.push(ConnectionGene {
+                    innovation: from * outputs + output,
+                    from,
+                    to: inputs + output,
+                    weight: rng.gen_range(-1.0..1.0),
+                    enabled: true,
+                });
+            }
+        }
+
+        NeatGenome {
+            inputs,
+            outputs,
+            hidden_activation,
+            nodes,
+            connections,
+        }
+    }
+
+    pub fn feedforward(&self, inputs: &Array1<f32>) -> Array1<f32> {
+        let mut values: HashMap<usize, f32> = HashMap::new();
+        for (id, value) in inputs.iter().enumerate() {
+            values.insert(id, *value);
+        }
+
+        for id in self.evaluation_order() {
+            let node = self.node(id).expect("ordered nodes exist");
+            if node.kind == NodeKind::Input {
+                continue;
+            }
+
+            let sum = self
+                .connections
+                .iter()
+                .filter(|connection| connection.enabled && connection.to == id)
+                .map(|connection| values.get(&connection.from).unwrap_or(&0.0) * connection.weight)
+                .sum::<f32>()
+                + node.bias;
+            values.insert(id, node.activation.apply(sum));
+        }
+
+        (0..self.outputs)
+            .map(|output| values[&(self.inputs + output)])
+            .collect()
+    }
+
+    pub fn hidden_neurons(&self) -> usize {
+        self.nodes
+            .iter()
+            .filter(|node| node.kind == NodeKind::Hidden)
+            .count()
+    }
+
+    pub fn enabled_connections(&self) -> usize {
+        self.connections
+            .iter()
+            .filter(|connection| connection.enabled)
+            .count()
+    }
+
+    pub fn mutate(
+        &mut self,
+        rng: &mut ChaCha12Rng,
+        innovations: &mut Innovations,
+        weight_rate: f32,
+        add_node_rate: f32,
+        add_connection_rate: f32,
+    ) {
+        for connection in self.connections.iter_mut() {
+            if rng.gen::<f32>() < weight_rate {
+                connection.weight += rng.gen_range(-0.1..0.1);
+            }
+        }
+        for node in self.nodes.iter_mut() {
+            if node.kind != NodeKind::Input && rng.gen::<f32>() < weight_rate {
+                node.bias += rng.gen_range(-0.1..0.1);
+            }
+        }
+
+        if rng.gen::<f32>() < add_connection_rate {
+            self.add_connection(rng, innovations);
+        }
+        if rng.gen::<f32>() < add_node_rate {
+            self.add_node(rng, innovations);
+        }
+    }
+
+    // Connects two unconnected nodes, keeping the network free of cycles.
+    pub fn add_connection(&mut self, rng: &mut ChaCha12Rng, innovations: &mut Innovations) {
+        let sources: Vec<usize> = self
+            .nodes
+            .iter()
+            .filter(|node| node.kind != NodeKind::Output)
+            .map(|node| node.id)
+            .collect();
+        let targets: Vec<usize> = self
+            .nodes
+            .iter()
+            .filter(|node| node.kind != NodeKind::Input)
+            .map(|node| node.id)
+            .collect();
+
+        for _ in 0..ADD_CONNECTION_ATTEMPTS {
+            let from = sources[rng.gen_range(0..sources.len())];
+            let to = targets[rng.gen_range(0..targets.len())];
+
+            let exists = self
+                .connections
+                .iter()
+                .any(|connection| connection.from == from && connection.to == to);
+            if from == to || exists || self.reaches(to, from) {
+                continue;
+            }
+
+            self.insert_connection(ConnectionGene {
+                innovation: innovations.connection(from, to),
+                from,
+                to,
+                weight: rng.gen_range(-1.0..1.0),
+                enabled: true,
+            });
+            return;
+        }
+    }
+
+    // Splits an enabled connection with a new hidden node. The incoming connection gets
+    // weight 1.0 and the outgoing one the old weight, so behavior barely changes at first.
+    pub fn add_node(&mut self, rng: &mut ChaCha12Rng, innovations: &mut Innovations) {
+        let enabled: Vec<usize> = (0..self.connections.len())
+            .filter(|&index| self.connections[index].enabled)
+            .collect();
+        if enabled.is_empty() {
+            return;
+        }
+
+        let index = enabled[rng.gen_range(0..enabled.len())];
+        let split = self.connections[index].clone();
+        let id = innovations.split_node(split.innovation);
+        // Re-enabled by crossover and split again; the node already exists here.
+        if self.node(id).is_some() {
+            return;
+        }
+
+        self.connections[index].enabled = false;
+        self.nodes.push(NodeGene {
+            id,
+            kind: NodeKind::Hidden,
+            bias: 0.0,
+            activation: self.hidden_activation,
+        });
+        self.insert_connection(ConnectionGene {
+            innovation: innovations.connection(split.from, id),
+            from: split.from,
+            to: id,
+            weight: 1.0,
+            enabled: true,
+        });
+        self.insert_connection(ConnectionGene {
+            innovation: innovations.connection(id, split.to),
+            from: id,
+            to: split.to,
+            weight: split.weight,
+            enabled: true,
+        });
+    }
+
+    // Matching genes come from either parent at random, disjoint and excess genes from the
+    // fitter one, so the child has the fitter parent's structure.
+    pub fn crossover(fitter: &NeatGenome, other: &NeatGenome, rng: &mut ChaCha12Rng) -> NeatGenome {
+        let connections = fitter
+            .connections
+            .iter()
+            .map(|gene| {
+                let Some(matching) = other.connection(gene.innovation) else {
+                    return gene.clone();
+                };
+                let mut child = if rng.gen_bool(0.5) {
+                    gene.clone()
+                } else {
+                    matching.clone()
+                };
+                // A gene disabled in either parent usually stays disabled.
+                child.enabled = if !gene.enabled || !matching.enabled {
+                    rng.gen_bool(0.25)
+                } else {
+                    true
+                };
+                child
+            })
+            .collect();
+
+        let nodes = fitter
+            .nodes
+            .iter()
+            .map(|node| match other.node(node.id) {
+                Some(matching) if rng.gen_bool(0.5) => NodeGene {
+                    bias: matching.bias,
+                    ..node.clone()
+                },
+                _ => node.clone(),
+            })
+            .collect();
+
+        NeatGenome {
+            nodes,
+            connections,
+            ..fitter.clone()
+        }
+    }
+
+    // The NEAT compatibility distance: excess, disjoint and average weight differences.
+    pub fn compatibility(&self, other: &NeatGenome) -> f32 {
+        let max_own = self.connections.last().map_or(0, |gene| gene.innovation);
+        let max_other = other.connections.last().map_or(0, |gene| gene.innovation);
+
+        let mut excess = 0;
+        let mut disjoint = 0;
+        let mut matching = 0;
+        let mut weight_difference = 0.0;
+
+        for gene in &self.connections {
+            match other.connection(gene.innovation) {
+                Some(matching_gene) => {
+                    matching += 1;
+                    weight_difference += (gene.weight - matching_gene.weight).abs();
+                }
+                None if gene.innovation > max_other => excess += 1,
+                None => disjoint += 1,
+            }
+        }
+        for gene in &other.connections {
+            if self.connection(gene.innovation).is_none() {
+                if gene.innovation > max_own {
+                    excess += 1;
+                } else {
+                    disjoint += 1;
+                }
+            }
+        }
+
+        let genes = self.connections.len().max(other.connections.len());
+        let normalizer = if genes > NORMALIZE_ABOVE_GENES {
+            genes as f32
+        } else {
+            1.0
+        };
+        let average_weight_difference = if matching == 0 {
+            0.0
+        } else {
+            weight_difference / matching as f32
+        };
+
+        EXCESS_COEFFICIENT * excess as f32 / normalizer
+            + DISJOINT_COEFFICIENT * disjoint as f32 / normalizer
+            + WEIGHT_COEFFICIENT * average_weight_difference
+    }
+
+    fn node(&self, id: usize) -> Option<&NodeGene> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    fn connection(&self, innovation: usize) -> Option<&ConnectionGene> {
+        self.connections
+            .binary_search_by_key(&innovation, |gene| gene.innovation)
+            .ok()
+            .map(|index| &self.connections[index])
+    }
+
+    fn insert_connection(&mut self, gene: ConnectionGene) {
+        let index = self
+            .connections
+            .partition_point(|other| other.innovation < gene.innovation);
+        self.connections.insert(index, gene);
+    }
+
+    // Whether `to` can be reached from `from`, counting disabled connections too since
+    // crossover can turn them back on.
+    fn reaches(&self, from: usize, to: usize) -> bool {
+        let mut stack = vec![from];
+        let mut seen = vec![from];
+
+        while let Some(id) = stack.pop() {
+            if id == to {
+                return true;
+            }
+            for connection in self.connections.iter().filter(|c| c.from == id) {
+                if !seen.contains(&connection.to) {
+                    seen.push(connection.to);
+                    stack.push(connection.to);
+                }
+            }
+        }
+
+        false
+    }
+
+    // Topological order over enabled connections, ties broken by node order.
+    fn evaluation_order(&self) -> Vec<usize> {
+        let mut incoming: HashMap<usize, usize> =
+            self.nodes.iter().map(|node| (node.id, 0)).collect();
+        for connection in self.connections.iter().filter(|c| c.enabled) {
+            *incoming.entry(connection.to).or_default() += 1;
+        }
+
+        let mut ready: Vec<usize> = self
+            .nodes
+            .iter()
+            .filter(|node| incoming[&node.id] == 0)
+            .map(|node| node.id)
+            .rev()
+            .collect();
+        let mut order = Vec::with_capacity(self.nodes.len());
+
+        while let Some(id) = ready.pop() {
+            order.push(id);
+            for connection in self
+                .connections
+                .iter()
+                .filter(|c| c.enabled && c.from == id)
+            {
+                let count = incoming.get_mut(&connection.to).expect("targets exist");
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(connection.to);
+                }
+            }
+        }
+
+        order
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Species {
+    pub id: usize,
+    pub representative: NeatGenome,
+    pub members: usize,
+}
+
+#[derive(Resource, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Speciation {
+    pub species: Vec<Species>,
+    next_id: usize,
+    // Day species were last assigned, they're regrouped once a day.
+    #[serde(default)]
+    pub last_day: i32,
+}
+
+impl Speciation {
+    // Puts each genome in the first species whose representative is within `threshold`,
+    // founding a new species otherwise. Species left without members go extinct.
+    pub fn speciate(&mut self, genomes: &[&NeatGenome], threshold: f32) -> Vec<usize> {
+        for species in self.species.iter_mut() {
+            species.members = 0;
+        }
+
+        let mut assigned = Vec::with_capacity(genomes.len());
+        let mut new_representatives: HashMap<usize, NeatGenome> = HashMap::new();
+
+        for genome in genomes {
+            let found = self
+                .species
+                .iter_mut()
+                .find(|species| species.representative.compatibility(genome) < threshold);
+
+            let id = match found {
+                Some(species) => {
+                    species.members += 1;
+                    species.id
+                }
+                None => {
+                    let id = self.next_id;
+                    self.next_id += 1;
+                    self.species.push(Species {
+                        id,
+                        representative: (*genome).clone(),
+                        members: 1,
+                    });
+                    id
+                }
+            };
+
+            new_representatives
+                .entry(id)
+                .or_insert_with(|| (*genome).clone());
+            assigned.push(id);
+        }
+
+        self.species.retain(|species| species.members > 0);
+        for species in self.species.iter_mut() {
+            if let Some(representative) = new_representatives.remove(&species.id) {
+                species.representative = representative;
+            }
+        }
+
+        assigned
+    }
+}
+
+// Maps with tuple keys don't fit JSON objects, so they're stored as lists of pairs.
+mod as_pairs {
+    use std::collections::HashMap;
+    use std::hash::Hash;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<(K, V)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn genome(rng: &mut ChaCha12Rng) -> NeatGenome {
+        NeatGenome::new(rng, 3, 2, Activation::Tanh, Activation::Sigmoid)
+    }
+
+    #[test]
+    fn starts_fully_connected() {
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        let genome = genome(&mut rng);
+
+        assert_eq!(genome.connections.len(), 6);
+        assert_eq!(genome.hidden_neurons(), 0);
+
+        let outputs = genome.feedforward(&ndarray::array![0.5, -0.5, 1.0]);
+        assert_eq!(outputs.len(), 2);
+        assert!(outputs.iter().all(|output| (0.0..=1.0).contains(output)));
+    }
+
+    #[test]
+    fn same_structural_change_gets_the_same_innovation() {
+        let mut rng = ChaCha12Rng::seed_from_u64(2);
+        let mut innovations = Innovations::default();
+        let mut first = genome(&mut rng);
+        let mut second = first.clone();
+
+        first.add_node(&mut ChaCha12Rng::seed_from_u64(3), &mut innovations);
+        second.add_node(&mut ChaCha12Rng::seed_from_u64(3), &mut innovations);
+
+        assert_eq!(first.nodes, second.nodes);
+        assert_eq!(
+            first
+                .connections
+                .iter()
+                .map(|c| c.innovation)
+                .collect::<Vec<_>>(),
+            second
+                .connections
+                .iter()
+                .map(|c| c.innovation)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(first.hidden_neurons(), 1);
+        assert_eq!(first.enabled_connections(), 7);
+    }
+
+    #[test]
+    fn structural_mutations_stay_acyclic() {
+        let mut rng = ChaCha12Rng::seed_from_u64(4);
+        let mut innovations = Innovations::default();
+        let mut genome = genome(&mut rng);
+
+        for _ in 0..200 {
+            genome.mutate(&mut rng, &mut innovations, 0.5, 0.3, 0.5);
+        }
+
+        assert!(genome.hidden_neurons() > 0);
+        assert_eq!(genome.evaluation_order().len(), genome.nodes.len());
+        assert!(genome
+            .connections
+            .windows(2)
+            .all(|pair| pair[0].innovation < pair[1].innovation));
+        assert!(genome
+            .feedforward(&ndarray::array![1.0, 0.0, -1.0])
+            .iter()
+            .all(|output| output.is_finite()));
+    }
+
+    #[test]
+    fn crossover_keeps_the_fitter_structure() {
+        let mut rng = ChaCha12Rng::seed_from_u64(5);
+        let mut innovations = Innovations::default();
+        let other = genome(&mut rng);
+        let mut fitter = other.clone();
+        for _ in 0..5 {
+            fitter.add_node(&mut rng, &mut innovations);
+        }
+
+        let child = NeatGenome::crossover(&fitter, &other, &mut rng);
+
+        assert_eq!(child.nodes.len(), fitter.nodes.len());
+        assert_eq!(
+            child
+                .connections
+                .iter()
+                .map(|c| c.innovation)
+                .collect::<Vec<_>>(),
+            fitter
+                .connections
+                .iter()
+                .map(|c| c.innovation)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn compatibility_grows_with_structural_differences() {
+        let mut rng = ChaCha12Rng::seed_from_u64(6);
+        let mut innovations = Innovations::default();
+        let genome = genome(&mut rng);
+        let mut changed = genome.clone();
+        for _ in 0..3 {
+            changed.add_node(&mut rng, &mut innovations);
+        }
+
+        assert_eq!(genome.compatibility(&genome), 0.0);
+        assert!(genome.compatibility(&changed) > 1.0);
+        assert_eq!(
+            genome.compatibility(&changed),
+            changed.compatibility(&genome)
+        );
+    }
+
+    #[test]
+    fn speciation_groups_compatible_genomes() {
+        let mut rng = ChaCha12Rng::seed_from_u64(7);
+        let mut innovations = Innovations::default();
+        let genome = genome(&mut rng);
+        let mut changed = genome.clone();
+        for _ in 0..4 {
+            changed.add_node(&mut rng, &mut innovations);
+        }
+        let mut speciation = Speciation::default();
+
+        let assigned = speciation.speciate(&[&genome, &changed, &genome], 1.0);
+
+        assert_eq!(assigned[0], assigned[2]);
+        assert_ne!(assigned[0], assigned[1]);
+        assert_eq!(speciation.species.len(), 2);
+
+        speciation.speciate(&[&changed], 1.0);
+        assert_eq!(speciation.species.len(), 1);
+    }
+}
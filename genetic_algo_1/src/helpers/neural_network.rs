@@ -1,80 +1,601 @@
-use ndarray::{Array1, Array2};
+use ndarray::{Array, Array1, Array2, Axis, Dimension};
 use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Activation {
+    Sigmoid,
+    Tanh,
+    Relu,
+    Linear,
+}
+
+impl Activation {
+    pub const ALL: [Activation; 4] = [
+        Activation::Sigmoid,
+        Activation::Tanh,
+        Activation::Relu,
+        Activation::Linear,
+    ];
+
+    pub fn apply(&self, x: f32) -> f32 {
+        match self {
+            Activation::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+            Activation::Tanh => x.tanh(),
+            Activation::Relu => x.max(0.0),
+            Activation::Linear => x,
+        }
+    }
+
+    // Maps an output of this activation onto 0..1, so outputs read the same whichever
+    // activation produced them.
+    pub fn unit(&self, y: f32) -> f32 {
+        match self {
+            Activation::Sigmoid => y,
+            Activation::Tanh => (y + 1.0) / 2.0,
+            Activation::Relu => y.tanh(),
+            Activation::Linear => Activation::Sigmoid.apply(y),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Crossover {
+    // Every value comes from either parent with equal odds.
+    Uniform,
+    // Values before a random cut come from the first parent, the rest from the second.
+    SinglePoint,
+    // Values are a weighted average of both parents, with one random weighting per array.
+    Blend,
+}
+
+impl Crossover {
+    pub const ALL: [Crossover; 3] = [Crossover::Uniform, Crossover::SinglePoint, Crossover::Blend];
+
+    // Combines two arrays of the same shape, value by value at the same index.
+    fn combine<D: Dimension>(
+        &self,
+        parent1: &Array<f32, D>,
+        parent2: &Array<f32, D>,
+        rng: &mut ChaCha12Rng,
+    ) -> Array<f32, D> {
+        let cut = rng.gen_range(0..=parent1.len());
+        let share = rng.gen_range(0.0..=1.0);
+
+        let values = parent1
+            .iter()
+            .zip(parent2.iter())
+            .enumerate()
+            .map(|(index, (&a, &b))| match self {
+                Crossover::Uniform => {
+                    if rng.gen_bool(0.5) {
+                        a
+                    } else {
+                        b
+                    }
+                }
+                Crossover::SinglePoint => {
+                    if index < cut {
+                        a
+                    } else {
+                        b
+                    }
+                }
+                Crossover::Blend => a * share + b * (1.0 - share),
+            })
+            .collect();
+
+        Array::from_shape_vec(parent1.raw_dim(), values).expect("parents have the same shape")
+    }
+}
+
+// Fully connected layer, `weights` is (inputs, neurons). Recurrent layers also feed their
+// previous outputs back in through `recurrent`, which is (neurons, neurons).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    pub weights: Array2<f32>,
+    pub biases: Array1<f32>,
+    pub activation: Activation,
+    pub recurrent: Option<Array2<f32>>,
+}
+
+impl Layer {
+    fn new(
+        rng: &mut ChaCha12Rng,
+        input_size: usize,
+        size: usize,
+        activation: Activation,
+        recurrent: bool,
+    ) -> Layer {
+        Layer {
+            weights: Array2::from_shape_fn((input_size, size), |_| rng.gen_range(-1.0..1.0)),
+            biases: Array1::from_shape_fn(size, |_| rng.gen_range(-1.0..1.0)),
+            activation,
+            // Small feedback weights so memory nudges the layer rather than drowning its inputs.
+            recurrent: recurrent
+                .then(|| Array2::from_shape_fn((size, size), |_| rng.gen_range(-0.5..0.5))),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.biases.len()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct NeuralNetwork {
-    pub weights_input_hidden: Array2<f32>,
-    pub weights_hidden_output: Array2<f32>,
-    pub biases_hidden: Array1<f32>,
-    pub biases_output: Array1<f32>,
+    pub layers: Vec<Layer>,
 }
 
 impl NeuralNetwork {
-    pub fn new(input_size: usize, hidden_size: usize, output_size: usize) -> Self {
-        let mut rng = rand::thread_rng();
+    // `sizes` lists every layer including inputs and outputs, e.g. [12, 10, 10, 5].
+    // With `recurrent` every hidden layer remembers its outputs from the previous tick.
+    pub fn new(
+        rng: &mut ChaCha12Rng,
+        sizes: &[usize],
+        hidden_activation: Activation,
+        output_activation: Activation,
+        recurrent: bool,
+    ) -> Self {
+        assert!(sizes.len() >= 2, "a network needs inputs and outputs");
 
-        Self {
-            weights_input_hidden: Array2::from_shape_fn((input_size, hidden_size), |_| {
-                rng.gen_range(-1.0..1.0)
-            }),
-            weights_hidden_output: Array2::from_shape_fn((hidden_size, output_size), |_| {
-                rng.gen_range(-1.0..1.0)
-            }),
-            biases_hidden: Array1::from_shape_fn(hidden_size, |_| rng.gen_range(-1.0..1.0)),
-            biases_output: Array1::from_shape_fn(output_size, |_| rng.gen_range(-1.0..1.0)),
-        }
+        let layers = sizes
+            .windows(2)
+            .enumerate()
+            .map(|(index, pair)| {
+                let output = index == sizes.len() - 2;
+                let activation = if output {
+                    output_activation
+                } else {
+                    hidden_activation
+                };
+                Layer::new(rng, pair[0], pair[1], activation, recurrent && !output)
+            })
+            .collect();
+
+        Self { layers }
     }
 
-    pub fn feedforward(&self, inputs: &Array1<f32>) -> Array1<f32> {
-        let hidden_inputs = inputs.dot(&self.weights_input_hidden) + &self.biases_hidden;
-        let hidden_outputs = hidden_inputs.mapv(Self::sigmoid);
+    // `memory` holds each layer's previous outputs (empty for layers that aren't recurrent)
+    // and is updated in place. Entries that don't fit the layer, e.g. after a structural
+    // mutation, start over from zeros.
+    pub fn feedforward(&self, inputs: &Array1<f32>, memory: &mut Vec<Array1<f32>>) -> Array1<f32> {
+        memory.resize(self.layers.len(), Array1::zeros(0));
+
+        self.layers
+            .iter()
+            .zip(memory.iter_mut())
+            .fold(inputs.clone(), |values, (layer, state)| {
+                let mut sum = values.dot(&layer.weights) + &layer.biases;
+                let Some(recurrent) = &layer.recurrent else {
+                    return sum.mapv(|x| layer.activation.apply(x));
+                };
 
-        let final_inputs = hidden_outputs.dot(&self.weights_hidden_output) + &self.biases_output;
-        final_inputs.mapv(Self::sigmoid)
+                if state.len() != layer.size() {
+                    *state = Array1::zeros(layer.size());
+                }
+                sum += &state.dot(recurrent);
+                *state = sum.mapv(|x| layer.activation.apply(x));
+                state.clone()
+            })
     }
 
-    fn sigmoid(x: f32) -> f32 {
-        1.0 / (1.0 + (-x).exp())
+    pub fn is_recurrent(&self) -> bool {
+        self.layers.iter().any(|layer| layer.recurrent.is_some())
     }
 
-    pub fn crossover(parent1: &NeuralNetwork, parent2: &NeuralNetwork) -> NeuralNetwork {
-        let mut rng = rand::thread_rng();
+    pub fn sizes(&self) -> Vec<usize> {
+        let mut sizes = vec![self.layers[0].weights.nrows()];
+        sizes.extend(self.layers.iter().map(Layer::size));
+        sizes
+    }
 
-        let weights_input_hidden = parent1.weights_input_hidden.mapv(|v| {
-            if rng.gen_bool(0.5) {
-                v
-            } else {
-                parent2.weights_input_hidden[[0, 0]]
+    pub fn hidden_neurons(&self) -> usize {
+        self.layers[..self.layers.len() - 1]
+            .iter()
+            .map(Layer::size)
+            .sum()
+    }
+
+    // Nudges each weight and bias by up to `amount` with probability `rate`.
+    pub fn mutate_weights(&mut self, rng: &mut ChaCha12Rng, rate: f32, amount: f32) {
+        for layer in self.layers.iter_mut() {
+            let recurrent = layer
+                .recurrent
+                .iter_mut()
+                .flat_map(|weights| weights.iter_mut());
+            for value in layer
+                .weights
+                .iter_mut()
+                .chain(layer.biases.iter_mut())
+                .chain(recurrent)
+            {
+                if rng.gen::<f32>() < rate {
+                    *value += rng.gen_range(-amount..amount);
+                }
             }
-        });
-        let weights_hidden_output = parent1.weights_hidden_output.mapv(|v| {
-            if rng.gen_bool(0.5) {
-                v
-            } else {
-                parent2.weights_hidden_output[[0, 0]]
+        }
+    }
+
+    // Adds a neuron to a random hidden layer; its outgoing weights start small so the
+    // network's behavior only shifts a little.
+    pub fn add_hidden_neuron(&mut self, rng: &mut ChaCha12Rng) {
+        if self.layers.len() < 2 {
+            return;
+        }
+        let index = rng.gen_range(0..self.layers.len() - 1);
+
+        let layer = &mut self.layers[index];
+        let incoming = Array1::from_shape_fn(layer.weights.nrows(), |_| rng.gen_range(-1.0..1.0));
+        layer
+            .weights
+            .push_column(incoming.view())
+            .expect("incoming weights match the layer inputs");
+        layer
+            .biases
+            .append(
+                Axis(0),
+                Array1::from_elem(1, rng.gen_range(-1.0..1.0)).view(),
+            )
+            .expect("biases are one dimensional");
+        if let Some(recurrent) = &mut layer.recurrent {
+            let from_old = Array1::from_shape_fn(recurrent.nrows(), |_| rng.gen_range(-0.5..0.5));
+            recurrent
+                .push_column(from_old.view())
+                .expect("recurrent weights are square");
+            let from_new = Array1::from_shape_fn(recurrent.ncols(), |_| rng.gen_range(-0.5..0.5));
+            recurrent
+                .push_row(from_new.view())
+                .expect("recurrent weights are square");
+        }
+
+        let next = &mut self.layers[index + 1];
+        let outgoing = Array1::from_shape_fn(next.size(), |_| rng.gen_range(-0.1..0.1));
+        next.weights
+            .push_row(outgoing.view())
+            .expect("outgoing weights match the next layer");
+    }
+
+    // Removes a random neuron from a random hidden layer, never emptying a layer.
+    pub fn remove_hidden_neuron(&mut self, rng: &mut ChaCha12Rng) {
+        if self.layers.len() < 2 {
+            return;
+        }
+        let index = rng.gen_range(0..self.layers.len() - 1);
+        let size = self.layers[index].size();
+        if size <= 1 {
+            return;
+        }
+
+        let neuron = rng.gen_range(0..size);
+        let kept: Vec<usize> = (0..size).filter(|&other| other != neuron).collect();
+
+        let layer = &mut self.layers[index];
+        layer.weights = layer.weights.select(Axis(1), &kept);
+        layer.biases = layer.biases.select(Axis(0), &kept);
+        if let Some(recurrent) = &mut layer.recurrent {
+            *recurrent = recurrent.select(Axis(0), &kept).select(Axis(1), &kept);
+        }
+
+        let next = &mut self.layers[index + 1];
+        next.weights = next.weights.select(Axis(0), &kept);
+    }
+
+    // Layers are combined one by one; a layer whose shape differs between parents (after
+    // structural mutations) is inherited from `parent1` as a whole.
+    pub fn crossover(
+        parent1: &NeuralNetwork,
+        parent2: &NeuralNetwork,
+        strategy: Crossover,
+        rng: &mut ChaCha12Rng,
+    ) -> NeuralNetwork {
+        let layers = parent1
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(index, layer)| match parent2.layers.get(index) {
+                Some(other) if other.weights.dim() == layer.weights.dim() => Layer {
+                    weights: strategy.combine(&layer.weights, &other.weights, rng),
+                    biases: strategy.combine(&layer.biases, &other.biases, rng),
+                    activation: layer.activation,
+                    recurrent: match (&layer.recurrent, &other.recurrent) {
+                        (Some(a), Some(b)) if a.dim() == b.dim() => {
+                            Some(strategy.combine(a, b, rng))
+                        }
+                        _ => layer.recurrent.clone(),
+                    },
+                },
+                _ => layer.clone(),
+            })
+            .collect();
+
+        NeuralNetwork { layers }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn network(sizes: &[usize]) -> NeuralNetwork {
+        NeuralNetwork::new(
+            &mut ChaCha12Rng::seed_from_u64(1),
+            sizes,
+            Activation::Relu,
+            Activation::Sigmoid,
+            false,
+        )
+    }
+
+    fn recurrent_network(sizes: &[usize]) -> NeuralNetwork {
+        NeuralNetwork::new(
+            &mut ChaCha12Rng::seed_from_u64(1),
+            sizes,
+            Activation::Tanh,
+            Activation::Sigmoid,
+            true,
+        )
+    }
+
+    #[test]
+    fn builds_any_number_of_layers() {
+        let network = network(&[4, 8, 6, 3]);
+
+        assert_eq!(network.sizes(), vec![4, 8, 6, 3]);
+        assert_eq!(network.hidden_neurons(), 14);
+        assert_eq!(network.layers[0].activation, Activation::Relu);
+        assert_eq!(network.layers[2].activation, Activation::Sigmoid);
+
+        let outputs = network.feedforward(&Array1::from_elem(4, 0.5), &mut Vec::new());
+        assert_eq!(outputs.len(), 3);
+        assert!(outputs.iter().all(|output| (0.0..=1.0).contains(output)));
+    }
+
+    #[test]
+    fn activations() {
+        assert_eq!(Activation::Sigmoid.apply(0.0), 0.5);
+        assert_eq!(Activation::Tanh.apply(0.0), 0.0);
+        assert_eq!(Activation::Relu.apply(-2.0), 0.0);
+        assert_eq!(Activation::Relu.apply(2.0), 2.0);
+        assert_eq!(Activation::Linear.apply(-2.0), -2.0);
+    }
+
+    #[test]
+    fn outputs_read_as_unit_values() {
+        assert_eq!(Activation::Sigmoid.unit(0.25), 0.25);
+        assert_eq!(Activation::Tanh.unit(-1.0), 0.0);
+        assert_eq!(Activation::Tanh.unit(0.0), 0.5);
+        assert_eq!(Activation::Tanh.unit(1.0), 1.0);
+        assert_eq!(Activation::Relu.unit(0.0), 0.0);
+        assert!(Activation::Relu.unit(10.0) > 0.99);
+        assert_eq!(Activation::Linear.unit(0.0), 0.5);
+        assert!(Activation::Linear.unit(-10.0) < 0.01);
+    }
+
+    // Any output activation picked in the UI has to reach both ends of the steering range.
+    #[test]
+    fn every_activation_spans_the_unit_range() {
+        for activation in Activation::ALL {
+            let low = activation.unit(activation.apply(-10.0));
+            let high = activation.unit(activation.apply(10.0));
+            assert!(low < 0.01, "{:?} bottoms out at {}", activation, low);
+            assert!(high > 0.99, "{:?} tops out at {}", activation, high);
+        }
+    }
+
+    #[test]
+    fn structural_mutations_keep_layers_connected() {
+        let mut rng = ChaCha12Rng::seed_from_u64(2);
+        let mut network = network(&[4, 3, 3, 2]);
+
+        for _ in 0..20 {
+            network.add_hidden_neuron(&mut rng);
+        }
+        assert_eq!(network.hidden_neurons(), 26);
+
+        for _ in 0..100 {
+            network.remove_hidden_neuron(&mut rng);
+        }
+        assert_eq!(network.sizes(), vec![4, 1, 1, 2]);
+
+        for pair in network.layers.windows(2) {
+            assert_eq!(pair[0].size(), pair[1].weights.nrows());
+        }
+        assert_eq!(
+            network
+                .feedforward(&Array1::zeros(4), &mut Vec::new())
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn networks_without_hidden_layers_ignore_structural_mutations() {
+        let mut rng = ChaCha12Rng::seed_from_u64(3);
+        let mut network = network(&[4, 2]);
+
+        network.add_hidden_neuron(&mut rng);
+        network.remove_hidden_neuron(&mut rng);
+
+        assert_eq!(network.sizes(), vec![4, 2]);
+    }
+
+    #[test]
+    fn recurrent_networks_remember_previous_ticks() {
+        let network = recurrent_network(&[3, 4, 4, 2]);
+        let inputs = Array1::from_elem(3, 1.0);
+        let mut memory = Vec::new();
+
+        let first = network.feedforward(&inputs, &mut memory);
+        let second = network.feedforward(&inputs, &mut memory);
+
+        assert!(network.is_recurrent());
+        assert!(network.layers[2].recurrent.is_none());
+        assert_eq!(first, network.feedforward(&inputs, &mut Vec::new()));
+        assert_ne!(first, second);
+        assert_eq!(
+            memory.iter().map(|state| state.len()).collect::<Vec<_>>(),
+            vec![4, 4, 0]
+        );
+    }
+
+    #[test]
+    fn recurrent_weights_follow_structural_mutations() {
+        let mut rng = ChaCha12Rng::seed_from_u64(4);
+        let mut network = recurrent_network(&[3, 4, 2]);
+        let mut memory = Vec::new();
+        network.feedforward(&Array1::zeros(3), &mut memory);
+
+        for _ in 0..10 {
+            network.add_hidden_neuron(&mut rng);
+            network.remove_hidden_neuron(&mut rng);
+            network.add_hidden_neuron(&mut rng);
+        }
+
+        let size = network.layers[0].size();
+        assert_eq!(
+            network.layers[0].recurrent.as_ref().unwrap().dim(),
+            (size, size)
+        );
+        network.feedforward(&Array1::zeros(3), &mut memory);
+        assert_eq!(memory[0].len(), size);
+
+        let child = NeuralNetwork::crossover(
+            &network,
+            &recurrent_network(&[3, 4, 2]),
+            Crossover::Uniform,
+            &mut rng,
+        );
+        assert_eq!(child.layers[0].recurrent, network.layers[0].recurrent);
+    }
+
+    fn parents(seed: u64) -> (NeuralNetwork, NeuralNetwork) {
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+        let sizes = [
+            rng.gen_range(1..6),
+            rng.gen_range(1..6),
+            rng.gen_range(1..6),
+        ];
+        let recurrent = rng.gen_bool(0.5);
+        let mut parent = || {
+            NeuralNetwork::new(
+                &mut rng,
+                &sizes,
+                Activation::Tanh,
+                Activation::Sigmoid,
+                recurrent,
+            )
+        };
+        (parent(), parent())
+    }
+
+    // Every value of a network in the same order, weights, biases then recurrent weights.
+    fn values(network: &NeuralNetwork) -> Vec<f32> {
+        network
+            .layers
+            .iter()
+            .flat_map(|layer| {
+                layer
+                    .weights
+                    .iter()
+                    .chain(layer.biases.iter())
+                    .chain(layer.recurrent.iter().flat_map(|weights| weights.iter()))
+                    .copied()
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn crossover_takes_each_value_from_a_parent_at_the_same_index() {
+        for seed in 0..200 {
+            let (parent1, parent2) = parents(seed);
+            let mut rng = ChaCha12Rng::seed_from_u64(seed);
+
+            for strategy in [Crossover::Uniform, Crossover::SinglePoint] {
+                let child = NeuralNetwork::crossover(&parent1, &parent2, strategy, &mut rng);
+
+                assert_eq!(child.sizes(), parent1.sizes());
+                for ((c, a), b) in values(&child)
+                    .iter()
+                    .zip(values(&parent1))
+                    .zip(values(&parent2))
+                {
+                    assert!(*c == a || *c == b, "{:?} seed {}", strategy, seed);
+                }
             }
-        });
+        }
+    }
+
+    #[test]
+    fn single_point_crossover_switches_parents_once_per_array() {
+        for seed in 0..200 {
+            let (parent1, parent2) = parents(seed);
+            let child = NeuralNetwork::crossover(
+                &parent1,
+                &parent2,
+                Crossover::SinglePoint,
+                &mut ChaCha12Rng::seed_from_u64(seed),
+            );
 
-        let biases_hidden = parent1.biases_hidden.mapv(|v| {
-            if rng.gen_bool(0.5) {
-                v
-            } else {
-                parent2.biases_hidden[0]
+            for (index, layer) in child.layers.iter().enumerate() {
+                let from_first: Vec<bool> = layer
+                    .weights
+                    .iter()
+                    .zip(parent1.layers[index].weights.iter())
+                    .map(|(c, a)| c == a)
+                    .collect();
+                // Once values stop coming from the first parent they never come back.
+                let cut = from_first
+                    .iter()
+                    .position(|&first| !first)
+                    .unwrap_or(from_first.len());
+                assert!(
+                    from_first[cut..].iter().all(|&first| !first),
+                    "seed {}",
+                    seed
+                );
             }
-        });
-        let biases_output = parent1.biases_output.mapv(|v| {
-            if rng.gen_bool(0.5) {
-                v
-            } else {
-                parent2.biases_output[0]
+        }
+    }
+
+    #[test]
+    fn blend_crossover_stays_between_parents() {
+        for seed in 0..200 {
+            let (parent1, parent2) = parents(seed);
+            let child = NeuralNetwork::crossover(
+                &parent1,
+                &parent2,
+                Crossover::Blend,
+                &mut ChaCha12Rng::seed_from_u64(seed),
+            );
+
+            for ((c, a), b) in values(&child)
+                .iter()
+                .zip(values(&parent1))
+                .zip(values(&parent2))
+            {
+                assert!(
+                    *c >= a.min(b) - 1e-6 && *c <= a.max(b) + 1e-6,
+                    "seed {}",
+                    seed
+                );
             }
-        });
+        }
+    }
+
+    #[test]
+    fn crossover_keeps_mismatched_layers_from_the_first_parent() {
+        let mut rng = ChaCha12Rng::seed_from_u64(5);
+        let parent1 = network(&[4, 3, 2]);
+        let mut parent2 = network(&[4, 3, 2]);
+        parent2.add_hidden_neuron(&mut rng);
 
-        NeuralNetwork {
-            weights_input_hidden,
-            weights_hidden_output,
-            biases_hidden,
-            biases_output,
+        for strategy in Crossover::ALL {
+            let child = NeuralNetwork::crossover(&parent1, &parent2, strategy, &mut rng);
+            assert_eq!(child, parent1);
         }
     }
 }
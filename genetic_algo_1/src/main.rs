@@ -1,3 +1,7 @@
+use std::collections::HashSet;
+use std::env;
+use std::path::PathBuf;
+use std::process;
 use std::time::Duration;
 
 use bevy::{
@@ -7,23 +11,91 @@ use bevy::{
 };
 use bevy_egui::EguiPlugin;
 use cell::Action;
-use common::CellSelected;
+use common::{CellSelected, Evolution};
+use generational::{Generations, Selection};
+use helpers::brain::{Brain, BrainKind};
+use helpers::neat::{Innovations, Speciation};
 use rand::SeedableRng;
 use rand_chacha::ChaCha12Rng;
+use spatial::SpatialIndex;
 
 mod cell;
 mod common;
 mod food;
+mod generational;
+mod headless;
 mod helpers;
+mod snapshot;
+mod spatial;
 mod ui;
 
+const USAGE: &str =
+    "Usage: genetic_algo_1 [--seed N] [--locomotion targets|steering] [--brain network|neat] [--recurrent] [--sexual]
+       [--generational [--selection tournament|roulette|rank|elitism] [--generation-days N]]
+       [--headless --days N [--out DIR]] [--benchmark]";
+
+const STARTING_CELLS: usize = 15;
+const STARTING_FOODS: usize = 100;
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    seed: Option<u64>,
+    locomotion: common::Locomotion,
+    brain_kind: BrainKind,
+    recurrent: bool,
+    sexual_reproduction: bool,
+    evolution: Evolution,
+    selection: Selection,
+    generation_days: i32,
+    headless: bool,
+    benchmark: bool,
+    days: i32,
+    out: PathBuf,
+}
+
 fn main() {
+    let options = parse_args(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+    // Without --seed a random one is picked, it's shown in the UI so a run can be repeated.
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let game_config = common::GameConfig {
+        seed,
+        locomotion: options.locomotion,
+        brain_kind: options.brain_kind,
+        recurrent: options.recurrent,
+        sexual_reproduction: options.sexual_reproduction,
+        evolution: options.evolution,
+        selection: options.selection,
+        generation_days: options.generation_days,
+        ..common::GAME_CONFIG
+    };
+
+    if options.benchmark {
+        headless::benchmark(game_config);
+        return;
+    }
+
+    if options.headless {
+        if let Err(err) = headless::run(game_config, options.days, &options.out) {
+            eprintln!("Headless run failed: {}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
     App::new()
         .add_plugins((DefaultPlugins, MeshPickingPlugin, EguiPlugin))
         .insert_resource(Time::<Fixed>::from_hz(60.0))
-        .insert_resource(common::GAME_CONFIG)
-        .insert_resource(common::RandomSource(ChaCha12Rng::from_entropy()))
+        .insert_resource(game_config)
+        .insert_resource(common::RandomSource(ChaCha12Rng::seed_from_u64(seed)))
         .insert_resource(common::CellSelected(None))
+        .init_resource::<Innovations>()
+        .init_resource::<Speciation>()
+        .init_resource::<Generations>()
+        .init_resource::<SpatialIndex>()
+        .init_resource::<food::FoodPatches>()
         .insert_resource(ClearColor(Color::hsl(186.0, 0.36, 0.71)))
         .add_systems(Startup, setup)
         .add_systems(
@@ -34,28 +106,109 @@ fn main() {
                 pause.run_if(input_just_pressed(KeyCode::Space)),
             ),
         )
-        .add_systems(
-            FixedUpdate,
-            (
-                day_cycle,
-                brain_process,
-                metabolism_process,
-                cell_actions,
-                game_tick,
-                move_cells,
-            ),
-        )
-        .add_plugins(ui::ui_plugin)
+        .add_systems(FixedUpdate, game_tick)
+        .add_plugins((simulation_plugin, ui::ui_plugin, snapshot::snapshot_plugin))
         .run();
 }
 
+// Systems that advance the world; ordered so a seed always replays the same way.
+fn simulation_plugin(app: &mut App) {
+    app.add_systems(
+        FixedUpdate,
+        (
+            day_cycle,
+            food::food_ecology,
+            generational::generation_cycle,
+            speciate_cells,
+            spatial::rebuild_index,
+            brain_process,
+            metabolism_process,
+            cell_actions,
+            move_cells,
+        )
+            .chain(),
+    );
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        seed: None,
+        locomotion: common::GAME_CONFIG.locomotion,
+        brain_kind: common::GAME_CONFIG.brain_kind,
+        recurrent: common::GAME_CONFIG.recurrent,
+        sexual_reproduction: common::GAME_CONFIG.sexual_reproduction,
+        evolution: common::GAME_CONFIG.evolution,
+        selection: common::GAME_CONFIG.selection,
+        generation_days: common::GAME_CONFIG.generation_days,
+        headless: false,
+        benchmark: false,
+        days: 100,
+        out: PathBuf::from("runs"),
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => options.seed = Some(parse_number(&next_value(&mut args, &arg)?, &arg)?),
+            "--locomotion" => {
+                options.locomotion = match next_value(&mut args, &arg)?.as_str() {
+                    "targets" => common::Locomotion::Targets,
+                    "steering" => common::Locomotion::Steering,
+                    other => return Err(format!("unknown locomotion {}", other)),
+                }
+            }
+            "--brain" => {
+                options.brain_kind = match next_value(&mut args, &arg)?.as_str() {
+                    "network" => BrainKind::Network,
+                    "neat" => BrainKind::Neat,
+                    other => return Err(format!("unknown brain {}", other)),
+                }
+            }
+            "--recurrent" => options.recurrent = true,
+            "--sexual" => options.sexual_reproduction = true,
+            "--generational" => options.evolution = Evolution::Generational,
+            "--selection" => {
+                options.selection = match next_value(&mut args, &arg)?.as_str() {
+                    "tournament" => Selection::Tournament,
+                    "roulette" => Selection::Roulette,
+                    "rank" => Selection::Rank,
+                    "elitism" => Selection::Elitism,
+                    other => return Err(format!("unknown selection {}", other)),
+                }
+            }
+            "--generation-days" => {
+                options.generation_days = parse_number(&next_value(&mut args, &arg)?, &arg)?
+            }
+            "--headless" => options.headless = true,
+            "--benchmark" => options.benchmark = true,
+            "--days" => options.days = parse_number(&next_value(&mut args, &arg)?, &arg)?,
+            "--out" => options.out = PathBuf::from(next_value(&mut args, &arg)?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("missing value for {}", flag))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, flag: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {} for {}", value, flag))
+}
+
 fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     game_config: Res<common::GameConfig>,
 ) {
-    let seeded_rng = ChaCha12Rng::from_entropy();
-
     commands.spawn(Camera2d);
     commands.insert_resource(common::DayNightCycleTimer(Timer::from_seconds(
         game_config.day_speed,
@@ -65,7 +218,6 @@ fn setup(
     let food_mesh: Handle<Mesh> = meshes.add(Rectangle::new(10.0, 10.0));
     let cell_mesh: Handle<Mesh> = meshes.add(Rectangle::new(10.0, 10.0));
 
-    commands.insert_resource(common::RandomSource(seeded_rng));
     commands.insert_resource(common::FoodMesh(food_mesh));
     commands.insert_resource(common::CellMesh(cell_mesh));
 }
@@ -77,10 +229,26 @@ fn start(
     cell_mesh: Res<common::CellMesh>,
     food_mesh: Res<common::FoodMesh>,
     mut seeded_rng: ResMut<common::RandomSource>,
+    mut innovations: ResMut<Innovations>,
+    mut speciation: ResMut<Speciation>,
+    mut generations: ResMut<Generations>,
+    mut patches: ResMut<food::FoodPatches>,
     cell_query: Query<Entity, (With<cell::Cell>, Without<food::Food>)>,
     food_query: Query<Entity, (With<food::Food>, Without<cell::Cell>)>,
 ) {
     game_config.paused = false;
+    game_config.current_day = 1;
+    game_config.dead_cells = 0;
+    // Restarting replays the run for the configured seed.
+    seeded_rng.0 = ChaCha12Rng::seed_from_u64(game_config.seed);
+    *innovations = Innovations::default();
+    *speciation = Speciation::default();
+    *generations = Generations {
+        started_day: game_config.current_day,
+        ..default()
+    };
+    *patches = food::FoodPatches::new(&mut seeded_rng.0, &game_config);
+
     for food in food_query.iter() {
         commands.entity(food).despawn();
     }
@@ -88,7 +256,11 @@ fn start(
         commands.entity(cell).despawn();
     }
 
-    for _ in 0..15 {
+    let population = match game_config.evolution {
+        Evolution::OpenEnded => STARTING_CELLS,
+        Evolution::Generational => game_config.population_size,
+    };
+    for _ in 0..population {
         commands
             .spawn(cell::Cell::new(
                 &mut seeded_rng.0,
@@ -99,14 +271,15 @@ fn start(
             .observe(select_cell);
     }
 
-    for _ in 0..100 {
-        commands.spawn(food::Food::new(
-            &mut seeded_rng.0,
-            &game_config,
-            &food_mesh.0,
-            &mut materials,
-        ));
-    }
+    food::spawn_starting_foods(
+        &mut commands,
+        STARTING_FOODS,
+        &mut seeded_rng.0,
+        &game_config,
+        &patches,
+        &food_mesh.0,
+        &mut materials,
+    );
 }
 
 fn pause(mut game_config: ResMut<common::GameConfig>) {
@@ -123,15 +296,12 @@ fn select_cell(
 }
 
 fn game_tick(
-    cell_query: Query<&cell::Cell, (With<cell::Cell>, Without<food::Food>)>,
-    food_query: Query<&food::Food, (With<food::Food>, Without<cell::Cell>)>,
+    cell_query: Query<&cell::Cell>,
+    index: Res<SpatialIndex>,
     selected_cel: Res<CellSelected>,
     mut gizmos: Gizmos,
     game_config: ResMut<common::GameConfig>,
 ) {
-    let cells_snapshot: Vec<cell::Cell> = cell_query.iter().map(|cell| cell.clone()).collect();
-    let foods_snapshot: Vec<food::Food> = food_query.iter().map(|food| food.clone()).collect();
-
     gizmos.rect_2d(
         Isometry2d::IDENTITY,
         Vec2::new(game_config.map_width + 10.0, game_config.map_height + 10.0),
@@ -141,7 +311,7 @@ fn game_tick(
     if let Some(selected) = &selected_cel.0 {
         for cell in cell_query.iter() {
             if cell.id.eq(&selected.id) {
-                cell.draw_vision(&mut gizmos, &cells_snapshot, &foods_snapshot);
+                cell.draw_vision(&mut gizmos, &index);
             }
         }
     }
@@ -181,21 +351,53 @@ fn day_cycle(
     }
 }
 
+// Regroups NEAT brains into species once a day.
+fn speciate_cells(
+    mut cell_query: Query<&mut cell::Cell>,
+    mut speciation: ResMut<Speciation>,
+    game_config: Res<common::GameConfig>,
+) {
+    if game_config.paused || speciation.last_day == game_config.current_day {
+        return;
+    }
+    speciation.last_day = game_config.current_day;
+
+    let mut cells: Vec<Mut<cell::Cell>> = cell_query
+        .iter_mut()
+        .filter(|cell| matches!(cell.brain, Brain::Neat(_)))
+        .collect();
+    let genomes: Vec<_> = cells
+        .iter()
+        .filter_map(|cell| match &cell.brain {
+            Brain::Neat(genome) => Some(genome.clone()),
+            Brain::Network(_) => None,
+        })
+        .collect();
+
+    let assigned = speciation.speciate(
+        &genomes.iter().collect::<Vec<_>>(),
+        game_config.neat_compatibility_threshold,
+    );
+    for (cell, species) in cells.iter_mut().zip(assigned) {
+        cell.species = Some(species);
+    }
+}
+
 fn brain_process(
-    mut cell_query: Query<&mut cell::Cell, (With<cell::Cell>, Without<food::Food>)>,
-    food_query: Query<&food::Food, (With<food::Food>, Without<cell::Cell>)>,
+    mut cell_query: Query<&mut cell::Cell>,
+    index: Res<SpatialIndex>,
     mut seeded_rng: ResMut<common::RandomSource>,
-    game_config: ResMut<common::GameConfig>,
+    game_config: Res<common::GameConfig>,
 ) {
     if game_config.paused {
         return;
     }
-    let cells_snapshot: Vec<cell::Cell> = cell_query.iter().map(|cell| cell.clone()).collect();
-    let foods_snapshot: Vec<food::Food> = food_query.iter().map(|food| food.clone()).collect();
 
-    for mut cell in cell_query.iter_mut() {
-        cell.process_brain(&mut seeded_rng.0, &cells_snapshot, &foods_snapshot);
-    }
+    let streams = seeded_rng.streams();
+    cell_query.par_iter_mut().for_each(|mut cell| {
+        let mut rng = streams.for_cell(cell.id);
+        cell.process_brain(&mut rng, &index, &game_config);
+    });
 }
 
 fn metabolism_process(
@@ -203,14 +405,22 @@ fn metabolism_process(
     mut cell_query: Query<(&mut cell::Cell, Entity)>,
     time: Res<Time>,
     mut game_config: ResMut<common::GameConfig>,
+    mut generations: ResMut<Generations>,
 ) {
     if game_config.paused {
         return;
     }
-    for (mut cell, entity) in cell_query.iter_mut() {
-        cell.process_metabolism(time.delta_secs());
+    cell_query.par_iter_mut().for_each(|(mut cell, _)| {
+        cell.process_metabolism(&game_config, time.delta_secs());
+    });
+
+    // Deaths touch shared state, so they're handled in query order after the parallel pass.
+    for (cell, entity) in cell_query.iter() {
         if cell.health <= 0.0 {
             game_config.dead_cells += 1;
+            if game_config.evolution == Evolution::Generational {
+                generations.dead.push(cell.clone());
+            }
             commands.entity(entity).despawn();
         }
     }
@@ -225,15 +435,25 @@ fn move_cells(
         return;
     }
     for (mut cell, mut transform) in cell_query.iter_mut() {
-        cell.movement(&mut transform, &game_config, time.delta_secs());
+        match game_config.locomotion {
+            common::Locomotion::Targets => {
+                cell.movement(&mut transform, &game_config, time.delta_secs())
+            }
+            common::Locomotion::Steering => {
+                cell.steer(&mut transform, &game_config, time.delta_secs())
+            }
+        }
     }
 }
 
 fn cell_actions(
     mut commands: Commands,
-    mut cell_query: Query<&mut cell::Cell, (With<cell::Cell>, Without<food::Food>)>,
-    food_query: Query<(&food::Food, Entity), (With<food::Food>, Without<cell::Cell>)>,
+    mut cell_query: Query<(Entity, &mut cell::Cell)>,
+    food_query: Query<&food::Food>,
+    index: Res<SpatialIndex>,
+    time: Res<Time>,
     mut seeded_rng: ResMut<common::RandomSource>,
+    mut innovations: ResMut<Innovations>,
     game_config: Res<common::GameConfig>,
     cell_mesh: Res<common::CellMesh>,
     mut materials: ResMut<Assets<ColorMaterial>>,
@@ -242,85 +462,131 @@ fn cell_actions(
         return;
     }
 
-    for mut cell in cell_query.iter_mut() {
-        if cell.energy > 90.0 {
-            let offspring = cell.create_offspring(
-                &mut seeded_rng.0,
-                &game_config,
-                &cell_mesh.0,
-                &mut materials,
-            );
-            commands.spawn(offspring).observe(select_cell);
+    // Generational runs only breed between generations.
+    let open_ended = game_config.evolution == Evolution::OpenEnded;
+    let entities: Vec<Entity> = cell_query.iter().map(|(entity, _)| entity).collect();
+    let mut eaten: HashSet<Entity> = HashSet::new();
+    // Each cell mates at most once per tick.
+    let mut mated: HashSet<Entity> = HashSet::new();
+    // Killed cells are left at zero health for `metabolism_process` to remove next tick.
+    let mut killed: HashSet<Entity> = HashSet::new();
+
+    for entity in entities {
+        if killed.contains(&entity) {
+            continue;
         }
+
+        if let Ok((_, mut cell)) = cell_query.get_mut(entity) {
+            if open_ended && cell.energy > 90.0 {
+                let offspring = cell.create_offspring(
+                    &mut seeded_rng.0,
+                    &mut innovations,
+                    &game_config,
+                    &cell_mesh.0,
+                    &mut materials,
+                );
+                commands.spawn(offspring).observe(select_cell);
+            }
+        }
+
+        if open_ended && game_config.sexual_reproduction && !mated.contains(&entity) {
+            if let Some((mate_entity, mate_position)) =
+                find_mate(entity, &cell_query, &index, &mated, &killed, &game_config)
+            {
+                let [(_, mut cell), (_, mut mate)] = cell_query
+                    .get_many_mut([entity, mate_entity])
+                    .expect("mates are distinct living cells");
+                let cell_position = Vec2::new(cell.pos_x, cell.pos_y);
+
+                if mate_position.distance(cell_position) < cell::MATING_RANGE {
+                    let offspring = cell.mate(
+                        &mate,
+                        &mut seeded_rng.0,
+                        &mut innovations,
+                        &game_config,
+                        &cell_mesh.0,
+                        &mut materials,
+                    );
+                    mate.pay_for_mating(&game_config.rewards);
+                    commands.spawn(offspring).observe(select_cell);
+                    mated.extend([entity, mate_entity]);
+                    cell.target_location = None;
+                } else if game_config.locomotion == common::Locomotion::Targets {
+                    cell.target_location = Some(mate_position);
+                }
+            }
+        }
+
+        let mut chasing = false;
+        if game_config.predation {
+            if let Some((prey_entity, prey_position)) =
+                find_prey(entity, &cell_query, &index, &killed)
+            {
+                let [(_, mut cell), (_, mut prey)] = cell_query
+                    .get_many_mut([entity, prey_entity])
+                    .expect("predator and prey are distinct living cells");
+                let cell_position = Vec2::new(cell.pos_x, cell.pos_y);
+
+                if prey_position.distance(cell_position) < cell::ATTACK_RANGE {
+                    if cell.attack(&mut prey, &game_config, time.delta_secs()) {
+                        killed.insert(prey_entity);
+                    }
+                } else if game_config.locomotion == common::Locomotion::Targets
+                    && cell.action == Action::GoingForFood
+                {
+                    cell.target_location = Some(prey_position);
+                    chasing = true;
+                }
+            }
+        }
+
+        let Ok((_, mut cell)) = cell_query.get_mut(entity) else {
+            continue;
+        };
         let cell_position = Vec2::new(cell.pos_x, cell.pos_y);
+
+        // Steering cells aim themselves, they eat whatever food they run into.
+        if game_config.locomotion == common::Locomotion::Steering {
+            let touching = index
+                .foods
+                .query(cell_position, 10.0)
+                .find(|(_, food_entity)| !eaten.contains(food_entity));
+            if let Some((_, &food_entity)) = touching {
+                if let Ok(food) = food_query.get(food_entity) {
+                    cell.eat(food, &game_config);
+                }
+                commands.entity(food_entity).despawn();
+                eaten.insert(food_entity);
+            }
+            continue;
+        }
+
         match cell.action {
-            // Action::Duplicate => {
-            //     if cell.energy < 90.0 {
-            //         return;
-            //     }
-            //     let offspring = cell.create_offspring(
-            //         &mut seeded_rng.0,
-            //         &game_config,
-            //         &cell_mesh.0,
-            //         &mut materials,
-            //     );
-            //     commands.spawn(offspring).observe(select_cell);
-            //     // let mut nearest = 100000.0;
-            //     // let mut found_mate = false;
-
-            //     // for other_cell in cells_snapshot.iter() {
-            //     //     if other_cell.id != cell.id
-            //     //         && other_cell.mature
-            //     //         && other_cell.action == Action::FindMate
-            //     //     {
-            //     //         let other_cell_position = Vec2::new(other_cell.pos_x, other_cell.pos_y);
-
-            //     //         if cell.is_within_vision_cone(other_cell_position) {
-            //     //             found_mate = true;
-            //     //             let distance = other_cell_position.distance(cell_position);
-
-            //     //             if distance < nearest {
-            //     //                 cell.target_location = Some(other_cell_position);
-            //     //                 nearest = distance;
-            //     //             }
-
-            //     //             if distance < 1.0 {
-            //     //                 let offspring = cell.create_offspring(
-            //     //                     &mut seeded_rng.0,
-            //     //                     &game_config,
-            //     //                     &cell_mesh.0,
-            //     //                     &mut materials,
-            //     //                     &other_cell,
-            //     //                 );
-            //     //                 commands.spawn(offspring).observe(select_cell);
-            //     //                 break;
-            //     //             }
-            //     //         }
-            //     //     }
-            //     // }
-
-            //     // if !found_mate {
-            //     //     cell.random_target(&mut seeded_rng.0, &game_config);
-            //     // }
-            // }
+            // Predators after prey leave food alone until they lose sight of it.
+            Action::GoingForFood if chasing => {}
             Action::GoingForFood => {
                 let mut nearest = 100000.0;
                 let mut found_food = false;
 
-                for (food, food_entity) in food_query.iter() {
-                    let food_position = Vec2::new(food.pos_x, food.pos_y);
-                    if cell.is_within_vision_cone(food_position) {
-                        let distance = food_position.distance(cell_position);
-                        if distance < nearest {
-                            found_food = true;
-                            cell.target_location = Some(food_position);
-                            nearest = distance;
-                        }
-                        if distance < 10.0 {
-                            cell.eat(&food);
-                            commands.entity(food_entity).despawn();
-                            cell.target_location = None;
+                for (food_position, &food_entity) in
+                    index.foods.query(cell_position, cell.genes.vision_range)
+                {
+                    if eaten.contains(&food_entity) || !cell.is_within_vision_cone(food_position) {
+                        continue;
+                    }
+                    let distance = food_position.distance(cell_position);
+                    if distance < nearest {
+                        found_food = true;
+                        cell.target_location = Some(food_position);
+                        nearest = distance;
+                    }
+                    if distance < 10.0 {
+                        if let Ok(food) = food_query.get(food_entity) {
+                            cell.eat(food, &game_config);
                         }
+                        commands.entity(food_entity).despawn();
+                        eaten.insert(food_entity);
+                        cell.target_location = None;
                     }
                 }
 
@@ -332,7 +598,8 @@ fn cell_actions(
                     }
                 }
             }
-            Action::MovingAround => {
+            // Heading for a mate is set above, without one in view the cell wanders.
+            Action::MovingAround | Action::SeekMate => {
                 if cell.target_location.is_none()
                     || cell_position.distance(cell.target_location.unwrap()) < 10.0
                 {
@@ -342,3 +609,262 @@ fn cell_actions(
         }
     }
 }
+
+// The nearest cell a predator sees that nobody killed yet.
+fn find_prey(
+    entity: Entity,
+    cell_query: &Query<(Entity, &mut cell::Cell)>,
+    index: &SpatialIndex,
+    killed: &HashSet<Entity>,
+) -> Option<(Entity, Vec2)> {
+    let (_, cell) = cell_query.get(entity).ok()?;
+    if !cell.is_predator() {
+        return None;
+    }
+    let cell_position = Vec2::new(cell.pos_x, cell.pos_y);
+
+    index
+        .cells
+        .query(cell_position, cell.genes.vision_range)
+        .filter(|(position, entry)| {
+            entry.entity != entity
+                && !killed.contains(&entry.entity)
+                && cell_query.contains(entry.entity)
+                && cell.is_within_vision_cone(*position)
+        })
+        .min_by(|a, b| {
+            a.0.distance(cell_position)
+                .total_cmp(&b.0.distance(cell_position))
+        })
+        .map(|(position, entry)| (entry.entity, position))
+}
+
+// The fittest cell in view that's able to mate and hasn't mated or been killed this tick, the
+// nearest on ties.
+fn find_mate(
+    entity: Entity,
+    cell_query: &Query<(Entity, &mut cell::Cell)>,
+    index: &SpatialIndex,
+    mated: &HashSet<Entity>,
+    killed: &HashSet<Entity>,
+    game_config: &common::GameConfig,
+) -> Option<(Entity, Vec2)> {
+    let (_, cell) = cell_query.get(entity).ok()?;
+    if cell.action != Action::SeekMate {
+        return None;
+    }
+    let cell_position = Vec2::new(cell.pos_x, cell.pos_y);
+
+    index
+        .cells
+        .query(cell_position, cell.genes.vision_range)
+        .filter(|(_, entry)| !mated.contains(&entry.entity) && !killed.contains(&entry.entity))
+        .filter_map(|(position, entry)| {
+            let (_, other) = cell_query.get(entry.entity).ok()?;
+            (cell.can_mate_with(other, game_config) && cell.is_within_vision_cone(position))
+                .then_some((entry.entity, position, other.fitness))
+        })
+        .max_by(|a, b| {
+            a.2.total_cmp(&b.2).then(
+                b.1.distance(cell_position)
+                    .total_cmp(&a.1.distance(cell_position)),
+            )
+        })
+        .map(|(entity, position, _)| (entity, position))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    const TICKS: usize = 600;
+
+    fn run(seed: u64) -> (Vec<cell::Cell>, Vec<food::Food>) {
+        run_config(common::GameConfig {
+            seed,
+            ..common::GAME_CONFIG
+        })
+    }
+
+    fn run_config(game_config: common::GameConfig) -> (Vec<cell::Cell>, Vec<food::Food>) {
+        let mut app = headless::build_app(game_config);
+        let world = app.world_mut();
+
+        for _ in 0..TICKS {
+            headless::step(world);
+        }
+
+        let cells = world.query::<&cell::Cell>().iter(world).cloned().collect();
+        let foods = world.query::<&food::Food>().iter(world).copied().collect();
+        (cells, foods)
+    }
+
+    #[test]
+    fn same_seed_produces_the_same_population() {
+        let (cells, foods) = run(7);
+
+        assert!(!cells.is_empty());
+        assert_eq!((cells, foods), run(7));
+    }
+
+    #[test]
+    fn neat_brains_replay_the_same_way() {
+        let game_config = common::GameConfig {
+            seed: 7,
+            brain_kind: BrainKind::Neat,
+            ..common::GAME_CONFIG
+        };
+        let (cells, foods) = run_config(game_config.clone());
+
+        assert!(cells
+            .iter()
+            .all(|cell| matches!(cell.brain, Brain::Neat(_)) && cell.species.is_some()));
+        assert_eq!((cells, foods), run_config(game_config));
+    }
+
+    #[test]
+    fn different_seeds_diverge() {
+        assert_ne!(run(7).0, run(8).0);
+    }
+
+    const THREADS_VAR: &str = "GENETIC_ALGO_TEST_THREADS";
+    const POPULATION_PREFIX: &str = "population: ";
+
+    // The compute pool is set up once per process, so every thread count gets a process of its
+    // own: the test runs itself again with `THREADS_VAR` set and compares what those print.
+    #[test]
+    fn thread_count_does_not_change_results() {
+        if let Ok(threads) = env::var(THREADS_VAR) {
+            let threads = threads.parse().unwrap();
+            bevy::tasks::ComputeTaskPool::get_or_init(|| {
+                bevy::tasks::TaskPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+            });
+            assert_eq!(bevy::tasks::ComputeTaskPool::get().thread_num(), threads);
+            let (cells, foods) = run(11);
+            println!(
+                "{}{}",
+                POPULATION_PREFIX,
+                serde_json::to_string(&(cells, foods)).unwrap()
+            );
+            return;
+        }
+
+        let population = |threads: usize| {
+            let output = process::Command::new(env::current_exe().unwrap())
+                .args([
+                    "--exact",
+                    "tests::thread_count_does_not_change_results",
+                    "--nocapture",
+                ])
+                .env(THREADS_VAR, threads.to_string())
+                .output()
+                .unwrap();
+            assert!(output.status.success(), "{:?}", output);
+            String::from_utf8(output.stdout)
+                .unwrap()
+                .lines()
+                .find_map(|line| {
+                    line.split_once(POPULATION_PREFIX)
+                        .map(|(_, population)| population.to_string())
+                })
+                .expect("the child run prints its population")
+        };
+
+        assert_eq!(population(1), population(4));
+    }
+
+    #[test]
+    fn killed_cells_are_never_picked_as_mates() {
+        let mut rng = ChaCha12Rng::seed_from_u64(6);
+        let mut materials = Assets::<ColorMaterial>::default();
+        let mut seeker = cell::Cell::new(
+            &mut rng,
+            &common::GAME_CONFIG,
+            &Handle::default(),
+            &mut materials,
+        )
+        .4;
+        seeker.age = 10;
+        seeker.energy = 80.0;
+        seeker.action = Action::SeekMate;
+        (seeker.pos_x, seeker.pos_y, seeker.rotation) = (0.0, 0.0, 0.0);
+        seeker.genes.vision_range = 200.0;
+        seeker.genes.vision_angle = 90.0;
+        // Same genes and brain, straight ahead in plain view.
+        let mut mate = seeker.clone();
+        mate.id = uuid::Uuid::from_u128(1);
+        mate.pos_y = 10.0;
+
+        let mut world = World::new();
+        world.init_resource::<SpatialIndex>();
+        let seeker = world.spawn(seeker).id();
+        let mate = world.spawn(mate).id();
+        world.run_system_once(spatial::rebuild_index).unwrap();
+
+        let mut find = |killed: HashSet<Entity>| {
+            world
+                .run_system_once(
+                    move |cell_query: Query<(Entity, &mut cell::Cell)>,
+                          index: Res<SpatialIndex>| {
+                        find_mate(
+                            seeker,
+                            &cell_query,
+                            &index,
+                            &HashSet::new(),
+                            &killed,
+                            &common::GAME_CONFIG,
+                        )
+                    },
+                )
+                .unwrap()
+        };
+
+        assert_eq!(find(HashSet::new()), Some((mate, Vec2::new(0.0, 10.0))));
+        assert_eq!(find(HashSet::from([mate])), None);
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let parse = |list: &[&str]| parse_args(list.iter().map(|arg| arg.to_string()));
+
+        assert_eq!(parse(&[]).unwrap().seed, None);
+        assert_eq!(parse(&["--seed", "42"]).unwrap().seed, Some(42));
+        assert_eq!(
+            parse(&["--locomotion", "steering"]).unwrap().locomotion,
+            common::Locomotion::Steering
+        );
+
+        let options = parse(&["--headless", "--days", "5", "--out", "batch"]).unwrap();
+        assert!(options.headless);
+        assert_eq!(options.days, 5);
+        assert_eq!(options.out, PathBuf::from("batch"));
+
+        assert!(parse(&["--seed", "x"]).is_err());
+        assert!(parse(&["--days"]).is_err());
+        assert_eq!(
+            parse(&["--brain", "neat"]).unwrap().brain_kind,
+            BrainKind::Neat
+        );
+        assert!(parse(&["--recurrent"]).unwrap().recurrent);
+        assert!(parse(&["--sexual"]).unwrap().sexual_reproduction);
+        assert!(parse(&["--benchmark"]).unwrap().benchmark);
+        let options = parse(&[
+            "--generational",
+            "--selection",
+            "rank",
+            "--generation-days",
+            "4",
+        ])
+        .unwrap();
+        assert_eq!(options.evolution, Evolution::Generational);
+        assert_eq!(options.selection, Selection::Rank);
+        assert_eq!(options.generation_days, 4);
+        assert!(parse(&["--selection", "best"]).is_err());
+        assert!(parse(&["--locomotion", "flying"]).is_err());
+        assert!(parse(&["--brain", "lstm"]).is_err());
+        assert!(parse(&["--speed"]).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use bevy::{input::common_conditions::input_just_pressed, prelude::*};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::cell;
+use crate::common;
+use crate::food;
+use crate::generational::Generations;
+use crate::helpers::neat::{Innovations, Speciation};
+
+pub const SNAPSHOT_FILE: &str = "world.json";
+
+#[derive(Event)]
+pub struct SaveWorld;
+
+#[derive(Event)]
+pub struct LoadWorld;
+
+// Everything needed to resume a run; the rng keeps its stream position and the day timer its
+// progress, so a loaded world continues exactly like the saved one would have.
+#[derive(Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    pub config: common::GameConfig,
+    #[serde(default)]
+    pub day_timer: Timer,
+    pub cells: Vec<cell::Cell>,
+    pub foods: Vec<food::Food>,
+    pub patches: food::FoodPatches,
+    pub rng: ChaCha12Rng,
+    pub innovations: Innovations,
+    pub speciation: Speciation,
+    pub generations: Generations,
+}
+
+impl WorldSnapshot {
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    pub fn load(path: &Path) -> io::Result<WorldSnapshot> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn capture(world: &mut World) -> WorldSnapshot {
+        WorldSnapshot {
+            config: world.resource::<common::GameConfig>().clone(),
+            day_timer: world.resource::<common::DayNightCycleTimer>().0.clone(),
+            cells: world.query::<&cell::Cell>().iter(world).cloned().collect(),
+            foods: world.query::<&food::Food>().iter(world).copied().collect(),
+            patches: world.resource::<food::FoodPatches>().clone(),
+            rng: world.resource::<common::RandomSource>().0.clone(),
+            innovations: world.resource::<Innovations>().clone(),
+            speciation: world.resource::<Speciation>().clone(),
+            generations: world.resource::<Generations>().clone(),
+        }
+    }
+
+    // Replaces the world's cells, food and simulation state with the snapshot's. Cells and food
+    // are spawned in the order they were captured in, so queries visit them in the same order.
+    pub fn restore(self, world: &mut World) {
+        let existing: Vec<Entity> = world
+            .query_filtered::<Entity, Or<(With<cell::Cell>, With<food::Food>)>>()
+            .iter(world)
+            .collect();
+        for entity in existing {
+            world.despawn(entity);
+        }
+
+        *world.resource_mut::<common::GameConfig>() = self.config;
+        world.resource_mut::<common::DayNightCycleTimer>().0 = self.day_timer;
+        *world.resource_mut::<food::FoodPatches>() = self.patches;
+        world.resource_mut::<common::RandomSource>().0 = self.rng;
+        *world.resource_mut::<Innovations>() = self.innovations;
+        *world.resource_mut::<Speciation>() = self.speciation;
+        *world.resource_mut::<Generations>() = self.generations;
+        world.resource_mut::<common::CellSelected>().0 = None;
+
+        let cell_mesh = world.resource::<common::CellMesh>().0.clone();
+        let food_mesh = world.resource::<common::FoodMesh>().0.clone();
+        let (cells, foods) = (self.cells, self.foods);
+        world.resource_scope(|world, mut materials: Mut<Assets<ColorMaterial>>| {
+            for cell in cells {
+                world
+                    .spawn(cell.into_bundle(&cell_mesh, &mut materials))
+                    .observe(crate::select_cell);
+            }
+            for food in foods {
+                world.spawn(food.into_bundle(&food_mesh, &mut materials));
+            }
+        });
+    }
+}
+
+pub fn snapshot_plugin(app: &mut App) {
+    app.add_event::<SaveWorld>()
+        .add_event::<LoadWorld>()
+        .add_systems(
+            Update,
+            (
+                request_save.run_if(input_just_pressed(KeyCode::F5)),
+                request_load.run_if(input_just_pressed(KeyCode::F9)),
+                save_world,
+                load_world,
+            )
+                .chain(),
+        );
+}
+
+fn request_save(mut events: EventWriter<SaveWorld>) {
+    events.send(SaveWorld);
+}
+
+fn request_load(mut events: EventWriter<LoadWorld>) {
+    events.send(LoadWorld);
+}
+
+fn save_world(world: &mut World) {
+    if world.resource_mut::<Events<SaveWorld>>().drain().count() == 0 {
+        return;
+    }
+
+    let snapshot = WorldSnapshot::capture(world);
+    match snapshot.save(Path::new(SNAPSHOT_FILE)) {
+        Ok(()) => info!(
+            "Saved day {} with {} cells to {}",
+            snapshot.config.current_day,
+            snapshot.cells.len(),
+            SNAPSHOT_FILE
+        ),
+        Err(err) => error!("Could not save {}: {}", SNAPSHOT_FILE, err),
+    }
+}
+
+fn load_world(world: &mut World) {
+    if world.resource_mut::<Events<LoadWorld>>().drain().count() == 0 {
+        return;
+    }
+
+    let snapshot = match WorldSnapshot::load(Path::new(SNAPSHOT_FILE)) {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            error!("Could not load {}: {}", SNAPSHOT_FILE, err);
+            return;
+        }
+    };
+
+    info!(
+        "Loaded day {} with {} cells from {}",
+        snapshot.config.current_day,
+        snapshot.cells.len(),
+        SNAPSHOT_FILE
+    );
+    snapshot.restore(world);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless;
+    use crate::helpers::brain::BrainKind;
+
+    fn population(world: &mut World) -> (Vec<cell::Cell>, Vec<food::Food>) {
+        let cells = world.query::<&cell::Cell>().iter(world).cloned().collect();
+        let foods = world.query::<&food::Food>().iter(world).copied().collect();
+        (cells, foods)
+    }
+
+    #[test]
+    fn loaded_worlds_continue_like_the_saved_one() {
+        let game_config = common::GameConfig {
+            seed: 9,
+            brain_kind: BrainKind::Neat,
+            ..common::GAME_CONFIG
+        };
+        let mut original = headless::build_app(game_config);
+        // Stops mid-day, so the timer's progress matters.
+        for _ in 0..150 {
+            headless::step(original.world_mut());
+        }
+
+        let path = std::env::temp_dir().join(format!("world-{}.json", std::process::id()));
+        WorldSnapshot::capture(original.world_mut())
+            .save(&path)
+            .unwrap();
+        let mut loaded = headless::build_app(common::GAME_CONFIG);
+        WorldSnapshot::load(&path)
+            .unwrap()
+            .restore(loaded.world_mut());
+        fs::remove_file(&path).unwrap();
+
+        for _ in 0..300 {
+            headless::step(original.world_mut());
+            headless::step(loaded.world_mut());
+        }
+
+        assert_eq!(
+            original
+                .world()
+                .resource::<common::GameConfig>()
+                .current_day,
+            loaded.world().resource::<common::GameConfig>().current_day
+        );
+        assert_eq!(
+            original.world().resource::<Speciation>(),
+            loaded.world().resource::<Speciation>()
+        );
+        assert_eq!(
+            population(original.world_mut()),
+            population(loaded.world_mut())
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use uuid::Uuid;
+
+use crate::cell::{Cell, Genes};
+use crate::food::Food;
+
+// Bucket side length; close to the smallest vision range so cone queries stay a few buckets wide.
+const GRID_CELL_SIZE: f32 = 100.0;
+
+// Uniform grid over the map. Buckets are cleared rather than dropped between rebuilds so
+// their allocations get reused, and queries walk buckets in a fixed order so results are
+// deterministic.
+pub struct Grid<T> {
+    cell_size: f32,
+    buckets: HashMap<(i32, i32), Vec<(Vec2, T)>>,
+    len: usize,
+}
+
+impl<T> Grid<T> {
+    pub fn new(cell_size: f32) -> Grid<T> {
+        Grid {
+            cell_size,
+            buckets: HashMap::new(),
+            len: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for bucket in self.buckets.values_mut() {
+            bucket.clear();
+        }
+        self.len = 0;
+    }
+
+    pub fn insert(&mut self, position: Vec2, item: T) {
+        self.buckets
+            .entry(self.key(position))
+            .or_default()
+            .push((position, item));
+        self.len += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // Everything within `radius` of `center`.
+    pub fn query(&self, center: Vec2, radius: f32) -> impl Iterator<Item = (Vec2, &T)> + '_ {
+        let (min_x, min_y) = self.key(center - Vec2::splat(radius));
+        let (max_x, max_y) = self.key(center + Vec2::splat(radius));
+
+        (min_x..=max_x)
+            .flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+            .filter_map(|key| self.buckets.get(&key))
+            .flatten()
+            .filter(move |(position, _)| position.distance(center) <= radius)
+            .map(|(position, item)| (*position, item))
+    }
+
+    fn key(&self, position: Vec2) -> (i32, i32) {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+        )
+    }
+}
+
+// What other cells need to know about a cell they might see.
+pub struct CellEntry {
+    pub entity: Entity,
+    pub id: Uuid,
+    pub genes: Genes,
+}
+
+#[derive(Resource)]
+pub struct SpatialIndex {
+    pub cells: Grid<CellEntry>,
+    pub foods: Grid<Entity>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        SpatialIndex {
+            cells: Grid::new(GRID_CELL_SIZE),
+            foods: Grid::new(GRID_CELL_SIZE),
+        }
+    }
+}
+
+// Rebuilt once per tick, before anything looks around.
+pub fn rebuild_index(
+    mut index: ResMut<SpatialIndex>,
+    cell_query: Query<(Entity, &Cell)>,
+    food_query: Query<(Entity, &Food)>,
+) {
+    index.cells.clear();
+    index.foods.clear();
+
+    for (entity, cell) in cell_query.iter() {
+        index.cells.insert(
+            Vec2::new(cell.pos_x, cell.pos_y),
+            CellEntry {
+                entity,
+                id: cell.id,
+                genes: cell.genes.clone(),
+            },
+        );
+    }
+    for (entity, food) in food_query.iter() {
+        index
+            .foods
+            .insert(Vec2::new(food.pos_x, food.pos_y), entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_match_a_linear_scan() {
+        let mut grid = Grid::new(10.0);
+        let points: Vec<Vec2> = (0..400)
+            .map(|i| Vec2::new((i * 37 % 200) as f32 - 100.0, (i * 91 % 150) as f32 - 75.0))
+            .collect();
+        for (index, point) in points.iter().enumerate() {
+            grid.insert(*point, index);
+        }
+
+        for (center, radius) in [(Vec2::ZERO, 25.0), (Vec2::new(-90.0, 60.0), 40.0)] {
+            let mut found: Vec<usize> = grid.query(center, radius).map(|(_, i)| *i).collect();
+            found.sort();
+            let expected: Vec<usize> = (0..points.len())
+                .filter(|&i| points[i].distance(center) <= radius)
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn clearing_keeps_nothing() {
+        let mut grid = Grid::new(10.0);
+        grid.insert(Vec2::new(5.0, 5.0), 1);
+        assert_eq!(grid.len(), 1);
+
+        grid.clear();
+
+        assert_eq!(grid.len(), 0);
+        assert_eq!(grid.query(Vec2::ZERO, 50.0).count(), 0);
+    }
+}
@@ -4,6 +4,11 @@ use bevy_egui::EguiContexts;
 use crate::cell;
 use crate::common;
 use crate::food;
+use crate::generational::{Generations, Selection};
+use crate::helpers::brain::BrainKind;
+use crate::helpers::neat::Speciation;
+use crate::helpers::neural_network::{Activation, Crossover};
+use crate::snapshot;
 
 pub fn ui_plugin(app: &mut App) {
     app.add_systems(Update, (ui_system, update_cell_info));
@@ -15,6 +20,10 @@ pub fn ui_system(
     query_foods: Query<&food::Food>,
     mut game_options: ResMut<common::GameConfig>,
     mut selected_cell: ResMut<common::CellSelected>,
+    speciation: Res<Speciation>,
+    generations: Res<Generations>,
+    mut save_events: EventWriter<snapshot::SaveWorld>,
+    mut load_events: EventWriter<snapshot::LoadWorld>,
 ) {
     let mut highest_generation = 0;
     let mut highest_offspring: Option<&cell::Cell> = None;
@@ -51,14 +60,212 @@ pub fn ui_system(
         ui.label(format!("Current Day: {}", game_options.current_day));
         ui.label(format!("Highest Generation: {}", highest_generation));
         ui.label(format!("Oldest: {}", oldest));
+        if game_options.brain_kind == BrainKind::Neat {
+            ui.label(format!("Species: {}", speciation.species.len()));
+        }
         ui.checkbox(&mut game_options.show_fittest, "Show Fittest");
         ui.checkbox(&mut game_options.debug_logs, "Debug Logs");
         ui.checkbox(&mut game_options.paused, "Pause");
-        ui.add(egui::Slider::new(&mut game_options.foods_per_day, 0..=10).text("Foods Per Day"));
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut game_options.seed));
+            ui.label("Seed (used on restart)");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Locomotion");
+            ui.radio_value(
+                &mut game_options.locomotion,
+                common::Locomotion::Targets,
+                "Targets",
+            );
+            ui.radio_value(
+                &mut game_options.locomotion,
+                common::Locomotion::Steering,
+                "Steering",
+            );
+        });
+        ui.add(
+            egui::Slider::new(&mut game_options.thrust_energy_cost, 0.0..=10.0)
+                .text("Thrust Energy Cost"),
+        );
+        ui.collapsing("Food", |ui| {
+            ui.label(format!(
+                "Season abundance: {:.2}",
+                food::season_abundance(&game_options)
+            ));
+            ui.add(
+                egui::Slider::new(&mut game_options.foods_per_day, 0..=10)
+                    .text("Scattered Foods Per Day"),
+            );
+            ui.add(
+                egui::Slider::new(&mut game_options.food_patches, 0..=30)
+                    .text("Patches (used on restart)"),
+            );
+            ui.add(
+                egui::Slider::new(&mut game_options.patch_radius, 10.0..=500.0)
+                    .text("Patch Radius"),
+            );
+            ui.add(
+                egui::Slider::new(&mut game_options.patch_capacity, 1..=100).text("Patch Capacity"),
+            );
+            ui.add(
+                egui::Slider::new(&mut game_options.regrowth_rate, 0.0..=30.0)
+                    .text("Regrowth (per patch per day)"),
+            );
+            ui.add(
+                egui::Slider::new(&mut game_options.food_decay, 0.0..=10.0).text("Decay (per day)"),
+            );
+            ui.add(
+                egui::Slider::new(&mut game_options.grass_nutrition, 1.0..=100.0)
+                    .text("Grass Nutrition"),
+            );
+            ui.add(
+                egui::Slider::new(&mut game_options.fruit_nutrition, 1.0..=100.0)
+                    .text("Fruit Nutrition"),
+            );
+            ui.add(
+                egui::Slider::new(&mut game_options.season_length, 1..=200)
+                    .text("Season Length (days)"),
+            );
+            ui.add(
+                egui::Slider::new(&mut game_options.season_amplitude, 0.0..=1.0)
+                    .text("Season Amplitude"),
+            );
+        });
         ui.add(egui::Slider::new(&mut game_options.mutation_rate, 0.1..=1.0).text("Mutation Rate"));
+        egui::ComboBox::from_label("Crossover")
+            .selected_text(format!("{:?}", game_options.crossover))
+            .show_ui(ui, |ui| {
+                for option in Crossover::ALL {
+                    ui.selectable_value(
+                        &mut game_options.crossover,
+                        option,
+                        format!("{:?}", option),
+                    );
+                }
+            });
+        ui.collapsing("Evolution", |ui| {
+            ui.horizontal(|ui| {
+                ui.radio_value(
+                    &mut game_options.evolution,
+                    common::Evolution::OpenEnded,
+                    "Open Ended",
+                );
+                ui.radio_value(
+                    &mut game_options.evolution,
+                    common::Evolution::Generational,
+                    "Generational",
+                );
+            });
+            if game_options.evolution == common::Evolution::Generational {
+                ui.label(format!(
+                    "Generation {}, day {} of {}",
+                    generations.current,
+                    game_options.current_day - generations.started_day,
+                    game_options.generation_days
+                ));
+                if let Some(last) = generations.history.last() {
+                    ui.label(format!(
+                        "Last generation: best {:.1}, average {:.1}, {} of {} survived",
+                        last.best_fitness, last.average_fitness, last.survivors, last.population
+                    ));
+                }
+            }
+            ui.add(
+                egui::Slider::new(&mut game_options.generation_days, 1..=100)
+                    .text("Generation Days"),
+            );
+            ui.add(
+                egui::Slider::new(&mut game_options.population_size, 2..=200)
+                    .text("Population (used on restart)"),
+            );
+            egui::ComboBox::from_label("Selection")
+                .selected_text(format!("{:?}", game_options.selection))
+                .show_ui(ui, |ui| {
+                    for option in Selection::ALL {
+                        ui.selectable_value(
+                            &mut game_options.selection,
+                            option,
+                            format!("{:?}", option),
+                        );
+                    }
+                });
+            ui.add(
+                egui::Slider::new(&mut game_options.tournament_size, 1..=10)
+                    .text("Tournament Size"),
+            );
+            ui.add(egui::Slider::new(&mut game_options.elite_count, 1..=50).text("Elite Count"));
+        });
+        ui.checkbox(&mut game_options.sexual_reproduction, "Sexual Reproduction");
+        ui.add(
+            egui::Slider::new(&mut game_options.mating_relatedness, 0.0..=1.0)
+                .text("Mating Relatedness"),
+        );
         ui.add(egui::Slider::new(&mut game_options.day_speed, 0.1..=100.0).text("Day Speed"));
         ui.add(egui::Slider::new(&mut game_options.map_height, 100.0..=20000.0).text("Map Height"));
         ui.add(egui::Slider::new(&mut game_options.map_width, 100.0..=20000.0).text("Map Width"));
+        ui.collapsing("Predation", |ui| {
+            ui.checkbox(&mut game_options.predation, "Carnivores Attack");
+            ui.add(
+                egui::Slider::new(&mut game_options.attack_damage, 0.0..=100.0)
+                    .text("Attack Damage (per second)"),
+            );
+            ui.add(
+                egui::Slider::new(&mut game_options.carcass_energy, 0.0..=100.0)
+                    .text("Carcass Energy"),
+            );
+        });
+        ui.collapsing("Brain (used on restart)", |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Kind");
+                ui.radio_value(&mut game_options.brain_kind, BrainKind::Network, "Network");
+                ui.radio_value(&mut game_options.brain_kind, BrainKind::Neat, "NEAT");
+            });
+            ui.add(egui::Slider::new(&mut game_options.hidden_layers, 0..=4).text("Hidden Layers"));
+            ui.add(
+                egui::Slider::new(&mut game_options.hidden_layer_size, 1..=32).text("Layer Size"),
+            );
+            activation_combo(ui, "Hidden Activation", &mut game_options.hidden_activation);
+            activation_combo(ui, "Output Activation", &mut game_options.output_activation);
+            ui.checkbox(&mut game_options.recurrent, "Recurrent Memory");
+            ui.add(
+                egui::Slider::new(&mut game_options.structural_mutation_rate, 0.0..=1.0)
+                    .text("Structural Mutation Rate"),
+            );
+            ui.add(
+                egui::Slider::new(&mut game_options.neat_add_node_rate, 0.0..=1.0)
+                    .text("NEAT Add Node Rate"),
+            );
+            ui.add(
+                egui::Slider::new(&mut game_options.neat_add_connection_rate, 0.0..=1.0)
+                    .text("NEAT Add Connection Rate"),
+            );
+            ui.add(
+                egui::Slider::new(&mut game_options.neat_compatibility_threshold, 0.5..=10.0)
+                    .text("NEAT Species Threshold"),
+            );
+        });
+        ui.collapsing("Rewards", |ui| {
+            let rewards = &mut game_options.rewards;
+            ui.add(
+                egui::Slider::new(&mut rewards.tick_alive, 0.0..=10.0).text("Alive (per second)"),
+            );
+            ui.add(egui::Slider::new(&mut rewards.found_food, 0.0..=50.0).text("Found Food"));
+            ui.add(egui::Slider::new(&mut rewards.ate_food, 0.0..=50.0).text("Ate Food"));
+            ui.add(
+                egui::Slider::new(&mut rewards.hunger, -50.0..=0.0).text("Starving (per second)"),
+            );
+            ui.add(egui::Slider::new(&mut rewards.reproduction, 0.0..=200.0).text("Reproduction"));
+            ui.add(egui::Slider::new(&mut rewards.kill, 0.0..=200.0).text("Kill"));
+        });
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui.button("Save (F5)").clicked() {
+                save_events.send(snapshot::SaveWorld);
+            }
+            if ui.button("Load (F9)").clicked() {
+                load_events.send(snapshot::LoadWorld);
+            }
+        });
     });
 
     egui::Window::new("Cell Viewer").show(contexts.ctx_mut(), |ui| {
@@ -72,16 +279,25 @@ pub fn ui_system(
                 ui.label(format!("Metabolism: {:?}", cell.genes.metabolism));
                 ui.label(format!("Energy Weight: {:?}", cell.genes.energy_weight));
                 ui.label(format!("Random Weight: {:?}", cell.genes.random_weight));
+                ui.label(format!("Diet: {:.2}", cell.genes.diet));
+                ui.label(format!("Size: {:.2}", cell.genes.size));
+                ui.label(format!("Strength: {:.2}", cell.genes.strength));
             });
             ui.collapsing("General Infos", |ui| {
                 ui.label(format!("Energy: {}", cell.energy));
                 ui.label(format!("Health: {}", cell.health));
                 ui.label(format!("Target: {:?}", cell.target_location));
                 ui.label(format!("Action: {:?}", cell.action));
-                ui.label(format!("Fitness: {:?}", cell.fitness));
+                ui.label(format!("Turn: {:.2}", cell.turn));
+                ui.label(format!("Thrust: {:.2}", cell.thrust));
+                ui.label(format!("Fitness: {:.1}", cell.fitness));
                 ui.label(format!("Age: {:?}", cell.age));
                 ui.label(format!("Generation: {:?}", cell.generation));
                 ui.label(format!("Offsprings: {:?}", cell.offsprings_count));
+                ui.label(format!("Brain: {}", cell.brain.describe()));
+                if let Some(species) = cell.species {
+                    ui.label(format!("Species: {}", species));
+                }
                 ui.label(format!("X: {:?}", cell.pos_x));
                 ui.label(format!("Y: {:?}", cell.pos_y));
             });
@@ -186,6 +402,16 @@ pub fn ui_system(
     // });
 }
 
+fn activation_combo(ui: &mut egui::Ui, label: &str, activation: &mut Activation) {
+    egui::ComboBox::from_label(label)
+        .selected_text(format!("{:?}", activation))
+        .show_ui(ui, |ui| {
+            for option in Activation::ALL {
+                ui.selectable_value(activation, option, format!("{:?}", option));
+            }
+        });
+}
+
 pub fn update_cell_info(
     mut selected_cell: ResMut<common::CellSelected>,
     queries: Query<&cell::Cell, With<cell::Cell>>,
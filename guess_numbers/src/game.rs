@@ -0,0 +1,217 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::ops::RangeInclusive;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn parse(input: &str) -> Option<Difficulty> {
+        match input.trim().to_lowercase().as_str() {
+            "1" | "e" | "easy" => Some(Difficulty::Easy),
+            "2" | "m" | "medium" => Some(Difficulty::Medium),
+            "3" | "h" | "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    pub fn range(&self) -> RangeInclusive<u32> {
+        match self {
+            Difficulty::Easy => 1..=10,
+            Difficulty::Medium => 1..=100,
+            Difficulty::Hard => 1..=1000,
+        }
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 5,
+            Difficulty::Medium => 8,
+            Difficulty::Hard => 10,
+        }
+    }
+
+    fn multiplier(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 1,
+            Difficulty::Medium => 2,
+            Difficulty::Hard => 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hint {
+    VeryClose,
+    Close,
+    Far,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GuessResult {
+    TooLow(Hint),
+    TooHigh(Hint),
+    OutOfRange,
+    Won { attempts: u32, score: u32 },
+    Lost { secret: u32 },
+}
+
+pub struct Game {
+    secret: u32,
+    difficulty: Difficulty,
+    attempts: u32,
+    finished: bool,
+}
+
+impl Game {
+    pub fn new(difficulty: Difficulty, rng: &mut impl Rng) -> Game {
+        Game::with_secret(difficulty, rng.gen_range(difficulty.range()))
+    }
+
+    pub fn with_secret(difficulty: Difficulty, secret: u32) -> Game {
+        Game {
+            secret,
+            difficulty,
+            attempts: 0,
+            finished: false,
+        }
+    }
+
+    pub fn attempts_left(&self) -> u32 {
+        self.difficulty.max_attempts() - self.attempts
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // Out-of-range guesses don't use up an attempt.
+    pub fn guess(&mut self, guess: u32) -> GuessResult {
+        if self.finished {
+            return GuessResult::Lost {
+                secret: self.secret,
+            };
+        }
+        if !self.difficulty.range().contains(&guess) {
+            return GuessResult::OutOfRange;
+        }
+
+        self.attempts += 1;
+
+        match guess.cmp(&self.secret) {
+            Ordering::Equal => {
+                self.finished = true;
+                GuessResult::Won {
+                    attempts: self.attempts,
+                    score: score(self.difficulty, self.attempts),
+                }
+            }
+            _ if self.attempts >= self.difficulty.max_attempts() => {
+                self.finished = true;
+                GuessResult::Lost {
+                    secret: self.secret,
+                }
+            }
+            Ordering::Less => GuessResult::TooLow(self.hint(guess)),
+            Ordering::Greater => GuessResult::TooHigh(self.hint(guess)),
+        }
+    }
+
+    // Distance thresholds scale with the range, so "very close" means the same on every level.
+    fn hint(&self, guess: u32) -> Hint {
+        let distance = guess.abs_diff(self.secret);
+        let span = self.difficulty.range().end() - self.difficulty.range().start() + 1;
+
+        if distance <= (span / 20).max(1) {
+            Hint::VeryClose
+        } else if distance <= span / 5 {
+            Hint::Close
+        } else {
+            Hint::Far
+        }
+    }
+}
+
+// Fewer attempts score higher; harder levels multiply the score.
+pub fn score(difficulty: Difficulty, attempts: u32) -> u32 {
+    let max_attempts = difficulty.max_attempts();
+    if attempts == 0 || attempts > max_attempts {
+        return 0;
+    }
+    100 * difficulty.multiplier() * (max_attempts - attempts + 1) / max_attempts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn seeded_games_are_reproducible() {
+        let first = Game::new(Difficulty::Hard, &mut StdRng::seed_from_u64(7));
+        let second = Game::new(Difficulty::Hard, &mut StdRng::seed_from_u64(7));
+
+        assert_eq!(first.secret, second.secret);
+        assert!(Difficulty::Hard.range().contains(&first.secret));
+    }
+
+    #[test]
+    fn guesses_report_direction_and_distance() {
+        let mut game = Game::with_secret(Difficulty::Medium, 50);
+
+        assert_eq!(game.guess(49), GuessResult::TooLow(Hint::VeryClose));
+        assert_eq!(game.guess(65), GuessResult::TooHigh(Hint::Close));
+        assert_eq!(game.guess(1), GuessResult::TooLow(Hint::Far));
+        assert_eq!(game.attempts_left(), 5);
+    }
+
+    #[test]
+    fn out_of_range_guesses_are_free() {
+        let mut game = Game::with_secret(Difficulty::Easy, 3);
+
+        assert_eq!(game.guess(0), GuessResult::OutOfRange);
+        assert_eq!(game.guess(11), GuessResult::OutOfRange);
+        assert_eq!(game.attempts, 0);
+    }
+
+    #[test]
+    fn winning_scores_by_attempts_used() {
+        let mut game = Game::with_secret(Difficulty::Easy, 3);
+
+        game.guess(5);
+        assert_eq!(
+            game.guess(3),
+            GuessResult::Won {
+                attempts: 2,
+                score: 80
+            }
+        );
+        assert!(game.is_finished());
+    }
+
+    #[test]
+    fn running_out_of_attempts_loses() {
+        let mut game = Game::with_secret(Difficulty::Easy, 3);
+
+        for _ in 0..4 {
+            assert!(matches!(game.guess(10), GuessResult::TooHigh(_)));
+        }
+
+        assert_eq!(game.guess(10), GuessResult::Lost { secret: 3 });
+        assert!(game.is_finished());
+    }
+
+    #[test]
+    fn score_scales_with_difficulty() {
+        assert_eq!(score(Difficulty::Easy, 1), 100);
+        assert_eq!(score(Difficulty::Hard, 1), 300);
+        assert_eq!(score(Difficulty::Hard, 10), 30);
+        assert_eq!(score(Difficulty::Medium, 9), 0);
+    }
+}
@@ -1,39 +1,353 @@
-use rand::Rng;
-use std::cmp::Ordering;
+use std::env;
 use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+
+mod game;
+mod server;
+mod solver;
+mod stats;
+
+use game::{Difficulty, Game, GuessResult, Hint};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use solver::{Answer, Bisection, Solver};
+use stats::{Outcome, ScoreBoard};
+
+const SCORES_FILE: &str = ".guess_numbers_scores.json";
+
+const BENCHMARK_SEED: u64 = 42;
+
+const USAGE: &str = "Usage: guess_numbers [--player NAME] [--scores FILE] [--stats] [--reverse]
+       guess_numbers --benchmark [--games N] [--seed N]
+       guess_numbers --serve ADDR [--seed N]
+       guess_numbers --connect ADDR [--room NAME] [--player NAME]";
+
+struct Options {
+    player: Option<String>,
+    scores: PathBuf,
+    show_stats: bool,
+    reverse: bool,
+    benchmark: bool,
+    games: usize,
+    seed: Option<u64>,
+    serve: Option<String>,
+    connect: Option<String>,
+    room: String,
+}
 
 fn main() {
+    let options = parse_args(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+
+    if options.benchmark {
+        run_benchmark(options.games, options.seed.unwrap_or(BENCHMARK_SEED));
+        return;
+    }
+
+    if let Some(addr) = &options.serve {
+        let rng = match options.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        if let Err(err) = server::serve(addr.as_str(), rng) {
+            eprintln!("Server error: {}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(addr) = &options.connect {
+        let player = match options.player.or_else(|| prompt("Player name: ")) {
+            Some(name) => server::player_name(&name),
+            None => return,
+        };
+        if let Err(err) = server::connect(addr.as_str(), &options.room, &player) {
+            eprintln!("Connection error: {}", err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if options.reverse {
+        reverse_game();
+        return;
+    }
+
+    let mut board = ScoreBoard::load(&options.scores).unwrap_or_else(|err| {
+        eprintln!("Could not read {}: {}", options.scores.display(), err);
+        process::exit(1);
+    });
+
+    if options.show_stats {
+        print_stats(&board);
+        return;
+    }
+
     println!("Guess the number!");
 
-    let secret_number = rand::thread_rng().gen_range(1..=10);
+    let player = match options.player {
+        Some(player) => player,
+        None => match prompt("Player name: ") {
+            Some(name) if !name.is_empty() => name,
+            Some(_) => String::from("player"),
+            None => return,
+        },
+    };
+
+    let mut rng = rand::thread_rng();
 
     loop {
-        print!("Please enter a number: ");
-        io::stdout().flush().unwrap();
+        let Some(difficulty) = choose_difficulty() else {
+            return;
+        };
+        let mut game = Game::new(difficulty, &mut rng);
+        let range = difficulty.range();
+
+        println!(
+            "I'm thinking of a number between {} and {}. You have {} attempts.",
+            range.start(),
+            range.end(),
+            difficulty.max_attempts()
+        );
+
+        let mut outcome = None;
+
+        while !game.is_finished() {
+            let Some(input) = prompt("Please enter a number: ") else {
+                return;
+            };
+            let guess: u32 = match input.parse() {
+                Ok(num) => num,
+                Err(_) => {
+                    println!("Enter a valid number");
+                    continue;
+                }
+            };
+
+            println!("You guessed: {}", guess);
+
+            match game.guess(guess) {
+                GuessResult::TooLow(hint) => {
+                    println!(
+                        "More ({}), {} attempts left",
+                        hint_text(hint),
+                        game.attempts_left()
+                    )
+                }
+                GuessResult::TooHigh(hint) => {
+                    println!(
+                        "Less ({}), {} attempts left",
+                        hint_text(hint),
+                        game.attempts_left()
+                    )
+                }
+                GuessResult::OutOfRange => {
+                    println!(
+                        "Pick a number between {} and {}",
+                        range.start(),
+                        range.end()
+                    )
+                }
+                GuessResult::Won { attempts, score } => {
+                    println!("You won in {} attempts! Score: {}", attempts, score);
+                    outcome = Some(Outcome {
+                        won: true,
+                        attempts,
+                        score,
+                    });
+                }
+                GuessResult::Lost { secret } => {
+                    println!("Out of attempts! The number was {}", secret);
+                    outcome = Some(Outcome {
+                        won: false,
+                        attempts: difficulty.max_attempts(),
+                        score: 0,
+                    });
+                }
+            }
+        }
+
+        if let Some(outcome) = outcome {
+            board.record(&player, difficulty, outcome);
+            if let Err(err) = board.save(&options.scores) {
+                eprintln!("Could not save scores: {}", err);
+            }
+            board.print_player(&player);
+            board.print_high_scores(5);
+        }
+
+        match prompt("Play again? (y/n): ") {
+            Some(answer) if answer.eq_ignore_ascii_case("y") => {}
+            _ => break,
+        }
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        player: None,
+        scores: env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(SCORES_FILE),
+        show_stats: false,
+        reverse: false,
+        benchmark: false,
+        games: 1000,
+        seed: None,
+        serve: None,
+        connect: None,
+        room: String::from("lobby"),
+    };
 
-        let mut guess = String::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--player" => options.player = Some(next_value(&mut args, &arg)?),
+            "--scores" => options.scores = PathBuf::from(next_value(&mut args, &arg)?),
+            "--stats" => options.show_stats = true,
+            "--reverse" => options.reverse = true,
+            "--benchmark" => options.benchmark = true,
+            "--games" => options.games = parse_number(&next_value(&mut args, &arg)?, &arg)?,
+            "--seed" => options.seed = Some(parse_number(&next_value(&mut args, &arg)?, &arg)?),
+            "--serve" => options.serve = Some(next_value(&mut args, &arg)?),
+            "--connect" => options.connect = Some(next_value(&mut args, &arg)?),
+            "--room" => options.room = next_value(&mut args, &arg)?,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("missing value for {}", flag))
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, flag: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {} for {}", value, flag))
+}
+
+fn reverse_game() {
+    println!("Think of a number and I'll guess it!");
 
-        io::stdin()
-            .read_line(&mut guess)
-            .expect("Failed to read line");
+    let Some(difficulty) = choose_difficulty() else {
+        return;
+    };
+    let range = difficulty.range();
+    let mut solver = Solver::new(range.clone());
+    let mut strategy = Bisection;
 
-        let guess: u32 = match guess.trim().parse() {
-            Ok(num) => num,
+    println!(
+        "Pick a number between {} and {}, then answer m (more), l (less) or c (correct).",
+        range.start(),
+        range.end()
+    );
+
+    loop {
+        let guess = match solver.next_guess(&mut strategy) {
+            Ok(guess) => guess,
             Err(_) => {
-                println!("Enter a valid number");
-                continue;
+                println!("Your answers contradict each other, no number is left!");
+                return;
             }
         };
 
-        println!("You guessed: {}", guess);
-
-        match guess.cmp(&secret_number) {
-            Ordering::Less => println!("More"),
-            Ordering::Greater => println!("Less"),
-            Ordering::Equal => {
-                println!("You won!");
-                break;
+        let answer = loop {
+            let Some(input) = prompt(&format!("Is it {}? ", guess)) else {
+                return;
+            };
+            match Answer::parse(&input) {
+                Some(answer) => break answer,
+                None => println!("Answer m, l or c"),
             }
+        };
+
+        if answer == Answer::Correct {
+            println!("Got it in {} guesses!", solver.guesses());
+            return;
+        }
+        solver.answer(answer);
+    }
+}
+
+fn run_benchmark(games: usize, seed: u64) {
+    println!("Benchmark: {} games per difficulty, seed {}", games, seed);
+
+    for difficulty in [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard] {
+        let range = difficulty.range();
+        println!(
+            "--- {:?} ({}-{}) ---",
+            difficulty,
+            range.start(),
+            range.end()
+        );
+
+        // The random strategy gets its own stream, sharing the seed would make it replay the secrets.
+        for mut strategy in solver::strategies(seed.wrapping_add(1)) {
+            let result = solver::benchmark(strategy.as_mut(), range.clone(), games, seed);
+            println!(
+                "{:<10} average {:>7.2} guesses, worst {}",
+                result.strategy, result.average_guesses, result.worst_guesses
+            );
+        }
+    }
+}
+
+fn print_stats(board: &ScoreBoard) {
+    println!("--- Players ---");
+    if board.players.is_empty() {
+        println!("No games played yet");
+    }
+    for player in board.players.keys() {
+        board.print_player(player);
+    }
+    board.print_high_scores(10);
+}
+
+fn choose_difficulty() -> Option<Difficulty> {
+    loop {
+        let choice =
+            prompt("Select difficulty - 1. Easy (1-10), 2. Medium (1-100), 3. Hard (1-1000): ")?;
+        match Difficulty::parse(&choice) {
+            Some(difficulty) => return Some(difficulty),
+            None => println!("Invalid choice"),
         }
     }
 }
+
+fn hint_text(hint: Hint) -> &'static str {
+    match hint {
+        Hint::VeryClose => "very close",
+        Hint::Close => "close",
+        Hint::Far => "far",
+    }
+}
+
+// Returns None once stdin is closed.
+fn prompt(message: &str) -> Option<String> {
+    print!("{}", message);
+    io::stdout().flush().unwrap();
+
+    let mut input = String::new();
+
+    let read = io::stdin()
+        .read_line(&mut input)
+        .expect("Failed to read line");
+
+    if read == 0 {
+        println!();
+        return None;
+    }
+
+    Some(input.trim().to_string())
+}
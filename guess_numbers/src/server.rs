@@ -0,0 +1,620 @@
+// Multiplayer rooms over a line protocol, one command or event per line.
+//
+// Client -> server:
+//   JOIN <room> <name>      join (or create) a room
+//   START [difficulty]      start a round in your room, medium by default
+//   GUESS <number>          guess on your turn
+//   QUIT                    leave and close the connection
+//
+// Server -> client:
+//   WELCOME <room> <name>, PLAYERS <a,b,..>, JOINED <name>, LEFT <name>,
+//   STARTED <low> <high>, TURN <name>, GUESSED <name> <number> MORE|LESS,
+//   WINNER <name> <number> <guesses>, BYE, ERROR <message>
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::ops::RangeInclusive;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::game::Difficulty;
+
+pub type ClientId = u64;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Join { room: String, name: String },
+    Start(Difficulty),
+    Guess(u32),
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let mut parts = line.split_whitespace();
+        let keyword = parts.next().unwrap_or_default().to_uppercase();
+        let args: Vec<&str> = parts.collect();
+
+        match (keyword.as_str(), args.as_slice()) {
+            ("JOIN", [room, name]) => Ok(Command::Join {
+                room: room.to_string(),
+                name: name.to_string(),
+            }),
+            ("JOIN", _) => Err(String::from("usage: JOIN <room> <name>")),
+            ("START", []) => Ok(Command::Start(Difficulty::Medium)),
+            ("START", [difficulty]) => Difficulty::parse(difficulty)
+                .map(Command::Start)
+                .ok_or_else(|| format!("unknown difficulty {}", difficulty)),
+            ("GUESS", [number]) => number
+                .parse()
+                .map(Command::Guess)
+                .map_err(|_| format!("invalid number {}", number)),
+            ("GUESS", _) => Err(String::from("usage: GUESS <number>")),
+            ("QUIT", []) => Ok(Command::Quit),
+            _ => Err(format!("unknown command {}", line.trim())),
+        }
+    }
+}
+
+struct Player {
+    id: ClientId,
+    name: String,
+    outbox: Sender<String>,
+}
+
+struct Round {
+    secret: u32,
+    range: RangeInclusive<u32>,
+    turn: usize,
+    guesses: u32,
+}
+
+#[derive(Default)]
+struct Room {
+    players: Vec<Player>,
+    round: Option<Round>,
+}
+
+impl Room {
+    fn broadcast(&self, message: &str) {
+        for player in &self.players {
+            // A closed outbox means the player is disconnecting, leave() cleans it up.
+            let _ = player.outbox.send(message.to_string());
+        }
+    }
+
+    fn announce_turn(&self) {
+        if let Some(round) = &self.round {
+            self.broadcast(&format!("TURN {}", self.players[round.turn].name));
+        }
+    }
+}
+
+pub struct Lobby {
+    rooms: HashMap<String, Room>,
+    members: HashMap<ClientId, String>,
+    rng: StdRng,
+}
+
+impl Lobby {
+    pub fn new(rng: StdRng) -> Lobby {
+        Lobby {
+            rooms: HashMap::new(),
+            members: HashMap::new(),
+            rng,
+        }
+    }
+
+    pub fn handle(&mut self, id: ClientId, outbox: &Sender<String>, command: Command) {
+        if let Err(err) = self.apply(id, outbox, command) {
+            let _ = outbox.send(format!("ERROR {}", err));
+        }
+    }
+
+    fn apply(
+        &mut self,
+        id: ClientId,
+        outbox: &Sender<String>,
+        command: Command,
+    ) -> Result<(), String> {
+        if let Command::Join { room, name } = command {
+            return self.join(id, outbox, room, name);
+        }
+        if command == Command::Quit {
+            let _ = outbox.send(String::from("BYE"));
+            return Ok(());
+        }
+
+        let room_name = self.members.get(&id).ok_or("join a room first")?;
+        let room = self
+            .rooms
+            .get_mut(room_name)
+            .expect("members point at rooms");
+
+        match command {
+            Command::Start(difficulty) => {
+                if room.round.is_some() {
+                    return Err(String::from("a round is already running"));
+                }
+                let range = difficulty.range();
+                room.round = Some(Round {
+                    secret: self.rng.gen_range(range.clone()),
+                    range: range.clone(),
+                    turn: 0,
+                    guesses: 0,
+                });
+                room.broadcast(&format!("STARTED {} {}", range.start(), range.end()));
+                room.announce_turn();
+            }
+            Command::Guess(guess) => {
+                let round = room.round.as_mut().ok_or("no round is running")?;
+                let player = &room.players[round.turn];
+                if player.id != id {
+                    return Err(format!("it is {}'s turn", player.name));
+                }
+                // Like the single-player game, out-of-range guesses don't use up the turn.
+                if !round.range.contains(&guess) {
+                    return Err(format!(
+                        "pick a number between {} and {}",
+                        round.range.start(),
+                        round.range.end()
+                    ));
+                }
+
+                round.guesses += 1;
+                let name = player.name.clone();
+
+                match round.secret.cmp(&guess) {
+                    Ordering::Equal => {
+                        let message = format!("WINNER {} {} {}", name, guess, round.guesses);
+                        room.round = None;
+                        room.broadcast(&message);
+                    }
+                    ordering => {
+                        let direction = if ordering == Ordering::Greater {
+                            "MORE"
+                        } else {
+                            "LESS"
+                        };
+                        round.turn = (round.turn + 1) % room.players.len();
+                        room.broadcast(&format!("GUESSED {} {} {}", name, guess, direction));
+                        room.announce_turn();
+                    }
+                }
+            }
+            Command::Join { .. } | Command::Quit => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    fn join(
+        &mut self,
+        id: ClientId,
+        outbox: &Sender<String>,
+        room_name: String,
+        name: String,
+    ) -> Result<(), String> {
+        if self.members.contains_key(&id) {
+            return Err(String::from("already in a room"));
+        }
+
+        let room = self.rooms.entry(room_name.clone()).or_default();
+        if room.players.iter().any(|player| player.name == name) {
+            return Err(format!("name {} is taken in {}", name, room_name));
+        }
+
+        room.broadcast(&format!("JOINED {}", name));
+        room.players.push(Player {
+            id,
+            name: name.clone(),
+            outbox: outbox.clone(),
+        });
+        self.members.insert(id, room_name.clone());
+
+        let names: Vec<&str> = room
+            .players
+            .iter()
+            .map(|player| player.name.as_str())
+            .collect();
+        let _ = outbox.send(format!("WELCOME {} {}", room_name, name));
+        let _ = outbox.send(format!("PLAYERS {}", names.join(",")));
+
+        // Late joiners take their turn after everyone already in the round.
+        if let Some(round) = &room.round {
+            let _ = outbox.send(format!(
+                "STARTED {} {}",
+                round.range.start(),
+                round.range.end()
+            ));
+            let _ = outbox.send(format!("TURN {}", room.players[round.turn].name));
+        }
+
+        Ok(())
+    }
+
+    pub fn leave(&mut self, id: ClientId) {
+        let Some(room_name) = self.members.remove(&id) else {
+            return;
+        };
+        let room = self
+            .rooms
+            .get_mut(&room_name)
+            .expect("members point at rooms");
+        let Some(index) = room.players.iter().position(|player| player.id == id) else {
+            return;
+        };
+        let player = room.players.remove(index);
+
+        if room.players.is_empty() {
+            self.rooms.remove(&room_name);
+            return;
+        }
+
+        room.broadcast(&format!("LEFT {}", player.name));
+
+        if let Some(round) = &mut room.round {
+            if index < round.turn {
+                round.turn -= 1;
+            } else if index == round.turn {
+                round.turn %= room.players.len();
+                room.announce_turn();
+            }
+        }
+    }
+}
+
+pub fn serve(addr: impl ToSocketAddrs, rng: StdRng) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    println!("Listening on {}", listener.local_addr()?);
+    run(listener, rng)
+}
+
+pub fn run(listener: TcpListener, rng: StdRng) -> io::Result<()> {
+    let lobby = Arc::new(Mutex::new(Lobby::new(rng)));
+
+    for (id, stream) in listener.incoming().enumerate() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("Connection failed: {}", err);
+                continue;
+            }
+        };
+        let lobby = Arc::clone(&lobby);
+
+        thread::spawn(move || {
+            if let Err(err) = handle_client(stream, id as ClientId, &lobby) {
+                eprintln!("Client {} error: {}", id, err);
+            }
+            lobby.lock().unwrap().leave(id as ClientId);
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_client(stream: TcpStream, id: ClientId, lobby: &Mutex<Lobby>) -> io::Result<()> {
+    let (outbox, inbox) = mpsc::channel::<String>();
+    let mut writer = stream.try_clone()?;
+
+    // Ends once the lobby and this connection have dropped their senders.
+    thread::spawn(move || {
+        for line in inbox {
+            if writeln!(writer, "{}", line).is_err() {
+                break;
+            }
+        }
+        let _ = writer.shutdown(Shutdown::Both);
+    });
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match Command::parse(&line) {
+            Ok(command) => {
+                let quit = command == Command::Quit;
+                lobby.lock().unwrap().handle(id, &outbox, command);
+                if quit {
+                    break;
+                }
+            }
+            Err(err) => {
+                let _ = outbox.send(format!("ERROR {}", err));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Names are sent as one protocol word, so whitespace inside them becomes underscores.
+pub fn player_name(name: &str) -> String {
+    let name = name.trim();
+    if name.is_empty() {
+        return String::from("player");
+    }
+    name.replace(char::is_whitespace, "_")
+}
+
+pub fn connect(addr: impl ToSocketAddrs, room: &str, name: &str) -> io::Result<()> {
+    let stream = TcpStream::connect(addr)?;
+    let mut writer = stream.try_clone()?;
+    writeln!(writer, "JOIN {} {}", room, name)?;
+
+    let printer = thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            match line {
+                Ok(line) => println!("{}", describe(&line)),
+                Err(_) => break,
+            }
+        }
+    });
+
+    println!("Type a number to guess, \"start [easy|medium|hard]\" or \"quit\".");
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        // Bare numbers are guesses, anything else is sent as a command.
+        if line.parse::<u32>().is_ok() {
+            writeln!(writer, "GUESS {}", line)?;
+        } else {
+            writeln!(writer, "{}", line)?;
+        }
+        if line.eq_ignore_ascii_case("quit") {
+            break;
+        }
+    }
+
+    // Closing stdin leaves the room too.
+    let _ = writeln!(writer, "QUIT");
+    let _ = printer.join();
+    Ok(())
+}
+
+fn describe(line: &str) -> String {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+
+    match parts.as_slice() {
+        ["WELCOME", room, name] => format!("Joined room {} as {}", room, name),
+        ["PLAYERS", names] => format!("Players: {}", names.replace(',', ", ")),
+        ["JOINED", name] => format!("{} joined", name),
+        ["LEFT", name] => format!("{} left", name),
+        ["STARTED", low, high] => format!("New round! Guess a number between {} and {}", low, high),
+        ["TURN", name] => format!("{}'s turn", name),
+        ["GUESSED", name, guess, "MORE"] => format!("{} guessed {}: More", name, guess),
+        ["GUESSED", name, guess, "LESS"] => format!("{} guessed {}: Less", name, guess),
+        ["WINNER", name, guess, guesses] => format!(
+            "{} wins! The number was {} ({} guesses in total)",
+            name, guess, guesses
+        ),
+        ["BYE"] => String::from("Bye!"),
+        ["ERROR", ..] => format!("Error: {}", line.trim_start_matches("ERROR ")),
+        _ => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use std::sync::mpsc::Receiver;
+
+    fn client() -> (Sender<String>, Receiver<String>) {
+        mpsc::channel()
+    }
+
+    fn drain(inbox: &Receiver<String>) -> Vec<String> {
+        inbox.try_iter().collect()
+    }
+
+    fn join(lobby: &mut Lobby, id: ClientId, outbox: &Sender<String>, name: &str) {
+        lobby.handle(
+            id,
+            outbox,
+            Command::Join {
+                room: String::from("room"),
+                name: name.to_string(),
+            },
+        );
+    }
+
+    fn secret(lobby: &Lobby) -> u32 {
+        lobby.rooms["room"].round.as_ref().unwrap().secret
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(
+            Command::parse("join room ana"),
+            Ok(Command::Join {
+                room: String::from("room"),
+                name: String::from("ana")
+            })
+        );
+        assert_eq!(
+            Command::parse("START"),
+            Ok(Command::Start(Difficulty::Medium))
+        );
+        assert_eq!(
+            Command::parse("start hard"),
+            Ok(Command::Start(Difficulty::Hard))
+        );
+        assert_eq!(Command::parse("GUESS 42"), Ok(Command::Guess(42)));
+        assert_eq!(Command::parse("quit"), Ok(Command::Quit));
+        assert!(Command::parse("GUESS many").is_err());
+        assert!(Command::parse("JOIN room").is_err());
+        assert!(Command::parse("DANCE").is_err());
+    }
+
+    #[test]
+    fn players_take_turns_and_first_correct_guess_wins() {
+        let mut lobby = Lobby::new(StdRng::seed_from_u64(1));
+        let (ana, ana_inbox) = client();
+        let (bob, bob_inbox) = client();
+
+        join(&mut lobby, 1, &ana, "ana");
+        join(&mut lobby, 2, &bob, "bob");
+        assert_eq!(
+            drain(&ana_inbox),
+            ["WELCOME room ana", "PLAYERS ana", "JOINED bob"]
+        );
+        assert_eq!(drain(&bob_inbox), ["WELCOME room bob", "PLAYERS ana,bob"]);
+
+        lobby.handle(2, &bob, Command::Start(Difficulty::Easy));
+        assert_eq!(drain(&ana_inbox), ["STARTED 1 10", "TURN ana"]);
+        drain(&bob_inbox);
+
+        lobby.handle(2, &bob, Command::Guess(1));
+        assert_eq!(drain(&bob_inbox), ["ERROR it is ana's turn"]);
+
+        let secret = secret(&lobby);
+        let wrong = if secret == 1 { 2 } else { 1 };
+        lobby.handle(1, &ana, Command::Guess(wrong));
+        assert_eq!(drain(&bob_inbox).last().unwrap(), "TURN bob");
+
+        lobby.handle(2, &bob, Command::Guess(secret));
+        let winner = format!("WINNER bob {} 2", secret);
+        assert_eq!(drain(&ana_inbox).last().unwrap(), &winner);
+        assert_eq!(drain(&bob_inbox), [winner]);
+        assert!(lobby.rooms["room"].round.is_none());
+    }
+
+    #[test]
+    fn out_of_range_guesses_keep_the_turn() {
+        let mut lobby = Lobby::new(StdRng::seed_from_u64(2));
+        let (ana, ana_inbox) = client();
+
+        join(&mut lobby, 1, &ana, "ana");
+        lobby.handle(1, &ana, Command::Start(Difficulty::Easy));
+        drain(&ana_inbox);
+
+        lobby.handle(1, &ana, Command::Guess(11));
+
+        assert_eq!(drain(&ana_inbox), ["ERROR pick a number between 1 and 10"]);
+        assert_eq!(lobby.rooms["room"].round.as_ref().unwrap().guesses, 0);
+    }
+
+    #[test]
+    fn player_names_are_one_word() {
+        assert_eq!(player_name("ana"), "ana");
+        assert_eq!(player_name(" ana maria\t"), "ana_maria");
+        assert_eq!(player_name("  "), "player");
+        assert_eq!(
+            Command::parse(&format!("JOIN room {}", player_name("a b"))),
+            Ok(Command::Join {
+                room: String::from("room"),
+                name: String::from("a_b"),
+            })
+        );
+    }
+
+    #[test]
+    fn commands_need_a_room_and_unique_names() {
+        let mut lobby = Lobby::new(StdRng::seed_from_u64(3));
+        let (ana, ana_inbox) = client();
+        let (other, other_inbox) = client();
+
+        lobby.handle(1, &ana, Command::Guess(5));
+        assert_eq!(drain(&ana_inbox), ["ERROR join a room first"]);
+
+        join(&mut lobby, 1, &ana, "ana");
+        join(&mut lobby, 2, &other, "ana");
+        assert_eq!(drain(&other_inbox), ["ERROR name ana is taken in room"]);
+
+        lobby.handle(1, &ana, Command::Guess(5));
+        assert_eq!(
+            drain(&ana_inbox).last().unwrap(),
+            "ERROR no round is running"
+        );
+    }
+
+    #[test]
+    fn leaving_on_your_turn_passes_it_on() {
+        let mut lobby = Lobby::new(StdRng::seed_from_u64(4));
+        let (ana, _ana_inbox) = client();
+        let (bob, bob_inbox) = client();
+        let (cid, _cid_inbox) = client();
+
+        join(&mut lobby, 1, &ana, "ana");
+        join(&mut lobby, 2, &bob, "bob");
+        join(&mut lobby, 3, &cid, "cid");
+        lobby.handle(1, &ana, Command::Start(Difficulty::Medium));
+        drain(&bob_inbox);
+
+        lobby.leave(1);
+
+        assert_eq!(drain(&bob_inbox), ["LEFT ana", "TURN bob"]);
+
+        lobby.leave(2);
+        lobby.leave(3);
+        assert!(lobby.rooms.is_empty());
+    }
+
+    fn read_until(reader: &mut impl BufRead, prefixes: &[&str]) -> String {
+        loop {
+            let mut line = String::new();
+            assert_ne!(reader.read_line(&mut line).unwrap(), 0, "connection closed");
+            if prefixes.iter().any(|prefix| line.starts_with(prefix)) {
+                return line.trim().to_string();
+            }
+        }
+    }
+
+    #[test]
+    fn plays_a_round_over_localhost() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || run(listener, StdRng::seed_from_u64(5)));
+
+        let mut players: Vec<(TcpStream, BufReader<TcpStream>)> = ["ana", "bob"]
+            .iter()
+            .map(|name| {
+                let mut stream = TcpStream::connect(addr).unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                writeln!(stream, "JOIN room {}", name).unwrap();
+                read_until(&mut reader, &["PLAYERS"]);
+                (stream, reader)
+            })
+            .collect();
+
+        writeln!(players[0].0, "START easy").unwrap();
+
+        // Players alternate, both bisecting on the answers everyone sees.
+        let (mut low, mut high) = (1, 10);
+        let mut turn = 0;
+        let winner = loop {
+            let guess = low + (high - low) / 2;
+            writeln!(players[turn].0, "GUESS {}", guess).unwrap();
+
+            let mut events = players
+                .iter_mut()
+                .map(|(_, reader)| read_until(reader, &["GUESSED", "WINNER"]));
+            let event = events.next().unwrap();
+            assert_eq!(events.next().unwrap(), event);
+
+            if event.starts_with("WINNER") {
+                break event;
+            }
+            if event.ends_with("MORE") {
+                low = guess + 1;
+            } else {
+                high = guess - 1;
+            }
+            turn = 1 - turn;
+        };
+
+        let name = ["ana", "bob"][turn];
+        assert!(winner.starts_with(&format!("WINNER {} ", name)));
+    }
+}
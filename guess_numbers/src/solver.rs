@@ -0,0 +1,275 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::Ordering;
+use std::ops::RangeInclusive;
+
+// What the secret is compared to the last guess, in the game's "More"/"Less" wording.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Answer {
+    More,
+    Less,
+    Correct,
+}
+
+impl Answer {
+    pub fn parse(input: &str) -> Option<Answer> {
+        match input.trim().to_lowercase().as_str() {
+            "m" | "more" | "+" | ">" => Some(Answer::More),
+            "l" | "less" | "-" | "<" => Some(Answer::Less),
+            "c" | "correct" | "=" => Some(Answer::Correct),
+            _ => None,
+        }
+    }
+
+    pub fn for_secret(secret: u32, guess: u32) -> Answer {
+        match secret.cmp(&guess) {
+            Ordering::Greater => Answer::More,
+            Ordering::Less => Answer::Less,
+            Ordering::Equal => Answer::Correct,
+        }
+    }
+}
+
+// Picks the next guess from the bounds still consistent with every answer so far.
+pub trait Strategy {
+    fn name(&self) -> &'static str;
+    fn guess(&mut self, low: u32, high: u32) -> u32;
+}
+
+pub struct Bisection;
+
+impl Strategy for Bisection {
+    fn name(&self) -> &'static str {
+        "bisection"
+    }
+
+    fn guess(&mut self, low: u32, high: u32) -> u32 {
+        low + (high - low) / 2
+    }
+}
+
+pub struct Linear;
+
+impl Strategy for Linear {
+    fn name(&self) -> &'static str {
+        "linear"
+    }
+
+    fn guess(&mut self, low: u32, _high: u32) -> u32 {
+        low
+    }
+}
+
+pub struct RandomGuess {
+    rng: StdRng,
+}
+
+impl RandomGuess {
+    pub fn new(seed: u64) -> RandomGuess {
+        RandomGuess {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Strategy for RandomGuess {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn guess(&mut self, low: u32, high: u32) -> u32 {
+        self.rng.gen_range(low..=high)
+    }
+}
+
+pub fn strategies(seed: u64) -> Vec<Box<dyn Strategy>> {
+    vec![
+        Box::new(Bisection),
+        Box::new(Linear),
+        Box::new(RandomGuess::new(seed)),
+    ]
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Inconsistent;
+
+pub struct Solver {
+    low: u32,
+    high: u32,
+    last_guess: Option<u32>,
+    guesses: u32,
+}
+
+impl Solver {
+    pub fn new(range: RangeInclusive<u32>) -> Solver {
+        Solver {
+            low: *range.start(),
+            high: *range.end(),
+            last_guess: None,
+            guesses: 0,
+        }
+    }
+
+    pub fn guesses(&self) -> u32 {
+        self.guesses
+    }
+
+    // Fails once the answers have ruled out every number.
+    pub fn next_guess(&mut self, strategy: &mut dyn Strategy) -> Result<u32, Inconsistent> {
+        if self.low > self.high {
+            return Err(Inconsistent);
+        }
+        let guess = strategy
+            .guess(self.low, self.high)
+            .clamp(self.low, self.high);
+        self.last_guess = Some(guess);
+        self.guesses += 1;
+        Ok(guess)
+    }
+
+    pub fn answer(&mut self, answer: Answer) {
+        let Some(guess) = self.last_guess else {
+            return;
+        };
+        match answer {
+            Answer::More => self.low = guess + 1,
+            Answer::Less => match guess.checked_sub(1) {
+                Some(high) => self.high = high,
+                // Nothing is below zero, leave the bounds empty so the next guess fails.
+                None => {
+                    self.low = 1;
+                    self.high = 0;
+                }
+            },
+            Answer::Correct => {
+                self.low = guess;
+                self.high = guess;
+            }
+        }
+    }
+}
+
+// Number of guesses `strategy` needs to find `secret`.
+pub fn solve(strategy: &mut dyn Strategy, range: RangeInclusive<u32>, secret: u32) -> u32 {
+    let mut solver = Solver::new(range);
+
+    loop {
+        let guess = solver
+            .next_guess(strategy)
+            .expect("answers from a real secret are always consistent");
+        let answer = Answer::for_secret(secret, guess);
+        if answer == Answer::Correct {
+            return solver.guesses();
+        }
+        solver.answer(answer);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BenchmarkResult {
+    pub strategy: &'static str,
+    pub games: usize,
+    pub average_guesses: f32,
+    pub worst_guesses: u32,
+}
+
+pub fn benchmark(
+    strategy: &mut dyn Strategy,
+    range: RangeInclusive<u32>,
+    games: usize,
+    seed: u64,
+) -> BenchmarkResult {
+    // Every strategy sees the same secrets for a given seed.
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut total = 0;
+    let mut worst = 0;
+
+    for _ in 0..games {
+        let secret = rng.gen_range(range.clone());
+        let guesses = solve(strategy, range.clone(), secret);
+        total += guesses;
+        worst = worst.max(guesses);
+    }
+
+    BenchmarkResult {
+        strategy: strategy.name(),
+        games,
+        average_guesses: if games == 0 {
+            0.0
+        } else {
+            total as f32 / games as f32
+        },
+        worst_guesses: worst,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bisection_needs_at_most_log2_guesses() {
+        for secret in 1..=100 {
+            assert!(solve(&mut Bisection, 1..=100, secret) <= 7);
+        }
+        for secret in 1..=1000 {
+            assert!(solve(&mut Bisection, 1..=1000, secret) <= 10);
+        }
+    }
+
+    #[test]
+    fn linear_walks_up_from_the_bottom() {
+        assert_eq!(solve(&mut Linear, 1..=10, 1), 1);
+        assert_eq!(solve(&mut Linear, 1..=10, 10), 10);
+    }
+
+    #[test]
+    fn random_strategy_always_finds_the_secret() {
+        let mut strategy = RandomGuess::new(3);
+
+        for secret in 1..=50 {
+            assert!(solve(&mut strategy, 1..=50, secret) <= 50);
+        }
+    }
+
+    #[test]
+    fn benchmark_is_reproducible_per_seed() {
+        let first = benchmark(&mut RandomGuess::new(1), 1..=100, 200, 42);
+        let second = benchmark(&mut RandomGuess::new(1), 1..=100, 200, 42);
+
+        assert_eq!(first, second);
+        assert_eq!(first.games, 200);
+    }
+
+    #[test]
+    fn bisection_beats_linear_on_average() {
+        let bisection = benchmark(&mut Bisection, 1..=100, 500, 7);
+        let linear = benchmark(&mut Linear, 1..=100, 500, 7);
+
+        assert!(bisection.average_guesses < linear.average_guesses);
+        assert!(bisection.worst_guesses <= 7);
+    }
+
+    #[test]
+    fn contradicting_answers_are_detected() {
+        let mut solver = Solver::new(1..=10);
+        let mut strategy = Bisection;
+
+        assert_eq!(solver.next_guess(&mut strategy), Ok(5));
+        solver.answer(Answer::More);
+        assert_eq!(solver.next_guess(&mut strategy), Ok(8));
+        solver.answer(Answer::Less);
+        assert_eq!(solver.next_guess(&mut strategy), Ok(6));
+        solver.answer(Answer::Less);
+
+        assert_eq!(solver.next_guess(&mut strategy), Err(Inconsistent));
+    }
+
+    #[test]
+    fn parses_answers() {
+        assert_eq!(Answer::parse("More"), Some(Answer::More));
+        assert_eq!(Answer::parse(" l "), Some(Answer::Less));
+        assert_eq!(Answer::parse("c"), Some(Answer::Correct));
+        assert_eq!(Answer::parse("maybe"), None);
+    }
+}
@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::game::Difficulty;
+
+const HIGH_SCORE_SLOTS: usize = 10;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub games_played: u32,
+    pub games_won: u32,
+    pub winning_attempts: u32,
+    pub current_streak: u32,
+    pub best_streak: u32,
+    pub best_score: u32,
+}
+
+impl PlayerStats {
+    pub fn win_rate(&self) -> f32 {
+        if self.games_played == 0 {
+            return 0.0;
+        }
+        self.games_won as f32 / self.games_played as f32
+    }
+
+    // Averaged over won games only, lost games always use every attempt.
+    pub fn average_attempts(&self) -> Option<f32> {
+        if self.games_won == 0 {
+            return None;
+        }
+        Some(self.winning_attempts as f32 / self.games_won as f32)
+    }
+
+    fn record(&mut self, outcome: &Outcome) {
+        self.games_played += 1;
+
+        if outcome.won {
+            self.games_won += 1;
+            self.winning_attempts += outcome.attempts;
+            self.current_streak += 1;
+            self.best_streak = self.best_streak.max(self.current_streak);
+            self.best_score = self.best_score.max(outcome.score);
+        } else {
+            self.current_streak = 0;
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub player: String,
+    pub score: u32,
+    pub attempts: u32,
+    pub difficulty: Difficulty,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outcome {
+    pub won: bool,
+    pub attempts: u32,
+    pub score: u32,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScoreBoard {
+    pub players: BTreeMap<String, PlayerStats>,
+    pub high_scores: Vec<HighScore>,
+}
+
+impl ScoreBoard {
+    // A missing file is an empty board; a corrupt one is an error so it isn't overwritten.
+    pub fn load(path: &Path) -> io::Result<ScoreBoard> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(ScoreBoard::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn record(&mut self, player: &str, difficulty: Difficulty, outcome: Outcome) {
+        self.players
+            .entry(player.to_string())
+            .or_default()
+            .record(&outcome);
+
+        if outcome.won {
+            self.high_scores.push(HighScore {
+                player: player.to_string(),
+                score: outcome.score,
+                attempts: outcome.attempts,
+                difficulty,
+            });
+            // Stable sort keeps the older entry first on ties.
+            self.high_scores
+                .sort_by_key(|entry| std::cmp::Reverse(entry.score));
+            self.high_scores.truncate(HIGH_SCORE_SLOTS);
+        }
+    }
+
+    pub fn player(&self, player: &str) -> Option<&PlayerStats> {
+        self.players.get(player)
+    }
+
+    pub fn print_player(&self, player: &str) {
+        let Some(stats) = self.player(player) else {
+            println!("No games played by {} yet", player);
+            return;
+        };
+
+        println!(
+            "{}: {} played, {:.0}% won, average attempts {}, best streak {}, best score {}",
+            player,
+            stats.games_played,
+            stats.win_rate() * 100.0,
+            stats
+                .average_attempts()
+                .map(|average| format!("{:.1}", average))
+                .unwrap_or_else(|| String::from("-")),
+            stats.best_streak,
+            stats.best_score
+        );
+    }
+
+    pub fn print_high_scores(&self, limit: usize) {
+        println!("--- High Scores ---");
+        if self.high_scores.is_empty() {
+            println!("No high scores yet");
+        }
+        for (position, entry) in self.high_scores.iter().take(limit).enumerate() {
+            println!(
+                "{}. {} - {} ({:?}, {} attempts)",
+                position + 1,
+                entry.player,
+                entry.score,
+                entry.difficulty,
+                entry.attempts
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn won(attempts: u32, score: u32) -> Outcome {
+        Outcome {
+            won: true,
+            attempts,
+            score,
+        }
+    }
+
+    fn lost(attempts: u32) -> Outcome {
+        Outcome {
+            won: false,
+            attempts,
+            score: 0,
+        }
+    }
+
+    #[test]
+    fn tracks_player_statistics() {
+        let mut board = ScoreBoard::default();
+
+        board.record("ana", Difficulty::Easy, won(2, 80));
+        board.record("ana", Difficulty::Easy, won(4, 40));
+        board.record("ana", Difficulty::Easy, lost(5));
+        board.record("ana", Difficulty::Easy, won(3, 60));
+
+        let stats = board.player("ana").unwrap();
+        assert_eq!(stats.games_played, 4);
+        assert_eq!(stats.games_won, 3);
+        assert_eq!(stats.win_rate(), 0.75);
+        assert_eq!(stats.average_attempts(), Some(3.0));
+        assert_eq!(stats.best_streak, 2);
+        assert_eq!(stats.current_streak, 1);
+        assert_eq!(stats.best_score, 80);
+    }
+
+    #[test]
+    fn players_without_wins_have_no_average() {
+        let mut board = ScoreBoard::default();
+
+        board.record("bob", Difficulty::Hard, lost(10));
+
+        assert_eq!(board.player("bob").unwrap().average_attempts(), None);
+        assert!(board.high_scores.is_empty());
+    }
+
+    #[test]
+    fn high_scores_are_sorted_and_capped() {
+        let mut board = ScoreBoard::default();
+
+        for score in 1..=12 {
+            board.record("ana", Difficulty::Medium, won(1, score * 10));
+        }
+        board.record("bob", Difficulty::Hard, won(1, 300));
+
+        assert_eq!(board.high_scores.len(), HIGH_SCORE_SLOTS);
+        assert_eq!(board.high_scores[0].player, "bob");
+        assert_eq!(board.high_scores[1].score, 120);
+        assert_eq!(board.high_scores.last().unwrap().score, 40);
+    }
+
+    #[test]
+    fn scoreboard_roundtrips_through_file() {
+        let path = env::temp_dir().join(format!("guess_numbers-{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(ScoreBoard::load(&path).unwrap(), ScoreBoard::default());
+
+        let mut board = ScoreBoard::default();
+        board.record("ana", Difficulty::Easy, won(2, 80));
+        board.save(&path).unwrap();
+
+        assert_eq!(ScoreBoard::load(&path).unwrap(), board);
+        fs::remove_file(&path).unwrap();
+    }
+}
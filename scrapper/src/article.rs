@@ -0,0 +1,237 @@
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::parse::collapse_whitespace;
+
+const WORDS_PER_MINUTE: usize = 200;
+
+// Tried in order; the first container found is treated as the article body.
+const BODY_SELECTORS: &[&str] = &["#article-body", "article", "main", "body"];
+const BLOCK_SELECTOR: &str = "p, h1, h2, h3, h4, h5, h6, li, pre, blockquote";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Article {
+    pub text: String,
+    pub published: Option<String>,
+    pub tags: Vec<String>,
+    pub reading_time_minutes: u32,
+    pub open_graph: BTreeMap<String, String>,
+    pub json_ld: Vec<serde_json::Value>,
+}
+
+pub fn extract(document: &Html) -> Article {
+    let text = main_text(document);
+    let json_ld = json_ld(document);
+
+    let published = meta_content(document, "meta[property=\"article:published_time\"]")
+        .into_iter()
+        .next()
+        .or_else(|| {
+            attr_values(document, "time[datetime]", "datetime")
+                .into_iter()
+                .next()
+        })
+        .or_else(|| {
+            json_ld.iter().find_map(|value| {
+                value
+                    .get("datePublished")
+                    .and_then(|date| date.as_str())
+                    .map(String::from)
+            })
+        });
+
+    Article {
+        reading_time_minutes: reading_time(&text),
+        text,
+        published,
+        tags: tags(document),
+        open_graph: open_graph(document),
+        json_ld,
+    }
+}
+
+fn main_text(document: &Html) -> String {
+    let block = Selector::parse(BLOCK_SELECTOR).unwrap();
+
+    for body in BODY_SELECTORS {
+        let selector = Selector::parse(body).unwrap();
+        if let Some(container) = document.select(&selector).next() {
+            let blocks: Vec<String> = container
+                .select(&block)
+                // Nested blocks (a <p> inside an <li>) are covered by their outer block.
+                .filter(|element| !has_block_ancestor(element, &container))
+                .map(|element| collapse_whitespace(&element.text().collect::<Vec<_>>().join("")))
+                .filter(|text| !text.is_empty())
+                .collect();
+
+            if blocks.is_empty() {
+                return collapse_whitespace(&container.text().collect::<Vec<_>>().join(" "));
+            }
+            return blocks.join("\n\n");
+        }
+    }
+
+    String::new()
+}
+
+fn has_block_ancestor(element: &ElementRef, container: &ElementRef) -> bool {
+    element
+        .ancestors()
+        .take_while(|node| node.id() != container.id())
+        .filter_map(ElementRef::wrap)
+        .any(|ancestor| {
+            matches!(
+                ancestor.value().name(),
+                "p" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "li" | "pre" | "blockquote"
+            )
+        })
+}
+
+fn tags(document: &Html) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+
+    let keywords: Vec<String> = meta_content(document, "meta[name=\"keywords\"]")
+        .iter()
+        .flat_map(|keywords| keywords.split(','))
+        .map(String::from)
+        .collect();
+
+    let candidates = meta_content(document, "meta[property=\"article:tag\"]")
+        .into_iter()
+        .chain(keywords)
+        .chain(text_values(document, "a.crayons-tag, a[rel=\"tag\"]"));
+
+    for tag in candidates {
+        let tag = tag.trim().trim_start_matches('#').to_lowercase();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    tags
+}
+
+fn open_graph(document: &Html) -> BTreeMap<String, String> {
+    let selector = Selector::parse("meta[property^=\"og:\"]").unwrap();
+
+    document
+        .select(&selector)
+        .filter_map(|element| {
+            let property = element.value().attr("property")?;
+            let content = element.value().attr("content")?;
+            Some((
+                property.trim_start_matches("og:").to_string(),
+                content.to_string(),
+            ))
+        })
+        .collect()
+}
+
+// Invalid blocks are skipped, top-level arrays are flattened.
+fn json_ld(document: &Html) -> Vec<serde_json::Value> {
+    let selector = Selector::parse("script[type=\"application/ld+json\"]").unwrap();
+
+    document
+        .select(&selector)
+        .filter_map(|element| {
+            serde_json::from_str::<serde_json::Value>(&element.text().collect::<String>()).ok()
+        })
+        .flat_map(|value| match value {
+            serde_json::Value::Array(values) => values,
+            value => vec![value],
+        })
+        .collect()
+}
+
+fn reading_time(text: &str) -> u32 {
+    let words = text.split_whitespace().count();
+    words.div_ceil(WORDS_PER_MINUTE) as u32
+}
+
+fn meta_content(document: &Html, selector: &str) -> Vec<String> {
+    attr_values(document, selector, "content")
+}
+
+fn attr_values(document: &Html, selector: &str, attr: &str) -> Vec<String> {
+    let selector = Selector::parse(selector).unwrap();
+
+    document
+        .select(&selector)
+        .filter_map(|element| element.value().attr(attr))
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+fn text_values(document: &Html, selector: &str) -> Vec<String> {
+    let selector = Selector::parse(selector).unwrap();
+
+    document
+        .select(&selector)
+        .map(|element| collapse_whitespace(&element.text().collect::<Vec<_>>().join("")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract_fixture(html: &str) -> Article {
+        extract(&Html::parse_document(html))
+    }
+
+    #[test]
+    fn extracts_article_body() {
+        let article = extract_fixture(include_str!("../tests/fixtures/article.html"));
+
+        assert_eq!(
+            article.text,
+            "Rust makes memory safety a compile-time guarantee.\n\nOwnership\n\n\
+             Every value has a single owner.\n\nMoves transfer ownership\n\nBorrows lend it"
+        );
+        assert_eq!(article.reading_time_minutes, 1);
+    }
+
+    #[test]
+    fn extracts_metadata() {
+        let article = extract_fixture(include_str!("../tests/fixtures/article.html"));
+
+        assert_eq!(article.published.as_deref(), Some("2024-01-15T10:00:00Z"));
+        assert_eq!(article.tags, vec!["rust", "beginners", "tutorial"]);
+        assert_eq!(
+            article.open_graph.get("title").map(String::as_str),
+            Some("Learning Rust, part 1")
+        );
+        assert_eq!(article.open_graph.len(), 4);
+        assert_eq!(article.json_ld.len(), 1);
+        assert_eq!(article.json_ld[0]["author"]["name"], "Rui Valim");
+    }
+
+    #[test]
+    fn pages_without_metadata_still_have_text() {
+        let article = extract_fixture(include_str!("../tests/fixtures/article_minimal.html"));
+
+        assert_eq!(article.text, "Just a paragraph with no metadata at all.");
+        assert_eq!(article.published, None);
+        assert!(article.tags.is_empty());
+        assert!(article.open_graph.is_empty());
+        assert!(article.json_ld.is_empty());
+    }
+
+    #[test]
+    fn reading_time_rounds_up() {
+        assert_eq!(reading_time(""), 0);
+        assert_eq!(reading_time("word"), 1);
+        assert_eq!(reading_time(&"word ".repeat(201)), 2);
+    }
+
+    #[test]
+    fn published_falls_back_to_json_ld() {
+        let article = extract_fixture(
+            r#"<script type="application/ld+json">[{"datePublished": "2023-05-01"}]</script>"#,
+        );
+
+        assert_eq!(article.published.as_deref(), Some("2023-05-01"));
+    }
+}
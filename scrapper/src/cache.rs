@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub body: String,
+}
+
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Cache { dir: dir.into() }
+    }
+
+    pub fn load(&self, url: &str) -> Option<CachedResponse> {
+        let contents = fs::read_to_string(self.path_for(url)).ok()?;
+        let cached: CachedResponse = serde_json::from_str(&contents).ok()?;
+
+        // Guard against hash collisions between different urls.
+        if cached.url == url {
+            Some(cached)
+        } else {
+            None
+        }
+    }
+
+    pub fn store(&self, cached: &CachedResponse) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let contents = serde_json::to_string(cached)?;
+        fs::write(self.path_for(&cached.url), contents)
+    }
+
+    fn path_for(&self, url: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.json", fnv1a(url.as_bytes())))
+    }
+}
+
+// Stable across builds, unlike std's DefaultHasher, so file names survive upgrades.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    pub fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("scrapper-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn stores_and_loads_responses() {
+        let cache = Cache::new(temp_dir("cache-roundtrip"));
+        let cached = CachedResponse {
+            url: String::from("https://dev.to/ruivalim/"),
+            etag: Some(String::from("\"abc\"")),
+            last_modified: None,
+            body: String::from("<html></html>"),
+        };
+
+        assert_eq!(cache.load(&cached.url), None);
+        cache.store(&cached).unwrap();
+
+        assert_eq!(cache.load(&cached.url), Some(cached));
+        assert_eq!(cache.load("https://dev.to/other/"), None);
+    }
+}
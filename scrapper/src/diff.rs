@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::item::{ScrapedItem, NO_LINK};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Diff {
+    pub added: Vec<ScrapedItem>,
+    pub removed: Vec<ScrapedItem>,
+    pub changed: Vec<(ScrapedItem, ScrapedItem)>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    pub fn print(&self) {
+        if self.is_empty() {
+            println!("No changes since the previous run");
+            return;
+        }
+        for item in self.added.iter() {
+            println!("+ {} - {}", item.title, item.link);
+        }
+        for item in self.removed.iter() {
+            println!("- {} - {}", item.title, item.link);
+        }
+        for (old, new) in self.changed.iter() {
+            println!("~ {} -> {} - {}", old.title, new.title, new.link);
+        }
+    }
+}
+
+// Link-less items all share the placeholder link, so they're matched by title instead.
+fn key(item: &ScrapedItem) -> (&str, &str) {
+    if item.link == NO_LINK {
+        (NO_LINK, item.title.as_str())
+    } else {
+        (item.link.as_str(), "")
+    }
+}
+
+// Items are matched by link, so a retitled post shows up as changed instead of added/removed.
+// Only title and link count as changes; articles come and go with `--deep`.
+pub fn diff(previous: &[ScrapedItem], current: &[ScrapedItem]) -> Diff {
+    let previous_by_key: HashMap<(&str, &str), &ScrapedItem> =
+        previous.iter().map(|item| (key(item), item)).collect();
+    let current_by_key: HashMap<(&str, &str), &ScrapedItem> =
+        current.iter().map(|item| (key(item), item)).collect();
+
+    let mut result = Diff::default();
+
+    for item in current.iter() {
+        match previous_by_key.get(&key(item)) {
+            None => result.added.push(item.clone()),
+            Some(old) if old.title != item.title || old.link != item.link => {
+                result.changed.push(((*old).clone(), item.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    for item in previous.iter() {
+        if !current_by_key.contains_key(&key(item)) {
+            result.removed.push(item.clone());
+        }
+    }
+
+    result
+}
+
+pub fn load_snapshot(path: &Path) -> Result<Vec<ScrapedItem>, Box<dyn Error>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+pub fn save_snapshot(path: &Path, items: &[ScrapedItem]) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(items)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::article::Article;
+    use crate::cache::tests::temp_dir;
+
+    fn item(title: &str, link: &str) -> ScrapedItem {
+        ScrapedItem {
+            title: title.to_string(),
+            link: link.to_string(),
+            source: String::from("https://dev.to/ruivalim/"),
+            article: None,
+        }
+    }
+
+    #[test]
+    fn reports_added_removed_and_changed() {
+        let previous = vec![
+            item("Old", "/old"),
+            item("Same", "/same"),
+            item("Draft", "/post"),
+        ];
+        let current = vec![
+            item("Same", "/same"),
+            item("Final", "/post"),
+            item("New", "/new"),
+        ];
+
+        let result = diff(&previous, &current);
+
+        assert_eq!(result.added, vec![item("New", "/new")]);
+        assert_eq!(result.removed, vec![item("Old", "/old")]);
+        assert_eq!(
+            result.changed,
+            vec![(item("Draft", "/post"), item("Final", "/post"))]
+        );
+    }
+
+    #[test]
+    fn identical_runs_have_no_changes() {
+        let items = vec![item("Same", "/same")];
+
+        assert!(diff(&items, &items).is_empty());
+    }
+
+    #[test]
+    fn link_less_items_are_matched_by_title() {
+        let previous = vec![item("Gone", NO_LINK), item("Kept", NO_LINK)];
+        let current = vec![item("Kept", NO_LINK), item("Fresh", NO_LINK)];
+
+        let result = diff(&previous, &current);
+
+        assert_eq!(result.added, vec![item("Fresh", NO_LINK)]);
+        assert_eq!(result.removed, vec![item("Gone", NO_LINK)]);
+        assert!(result.changed.is_empty());
+    }
+
+    #[test]
+    fn articles_are_not_changes() {
+        let shallow = vec![item("Same", "/same")];
+        let mut deep = shallow.clone();
+        deep[0].article = Some(Article {
+            text: String::from("Text"),
+            ..Article::default()
+        });
+
+        assert!(diff(&shallow, &deep).is_empty());
+        assert!(diff(&deep, &shallow).is_empty());
+    }
+
+    #[test]
+    fn snapshots_roundtrip() {
+        let path = temp_dir("snapshot").join("last_run.json");
+        let items = vec![item("Same", "/same")];
+
+        assert_eq!(load_snapshot(&path).unwrap(), Vec::new());
+        save_snapshot(&path, &items).unwrap();
+
+        assert_eq!(load_snapshot(&path).unwrap(), items);
+    }
+}
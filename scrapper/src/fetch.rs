@@ -0,0 +1,599 @@
+use reqwest::header::{
+    HeaderMap, HeaderValue, ACCEPT, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    RETRY_AFTER, USER_AGENT,
+};
+use reqwest::{Client, Response, StatusCode, Url};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OnceCell, Semaphore};
+
+use crate::cache::{Cache, CachedResponse};
+use crate::robots::Robots;
+
+pub const BROWSER_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
+
+#[derive(Debug, Clone)]
+pub struct FetchConfig {
+    pub timeout: Duration,
+    pub max_retries: u32,
+    pub backoff_base: Duration,
+    pub max_backoff: Duration,
+    pub min_host_interval: Duration,
+    pub concurrency: usize,
+    pub per_host_connections: usize,
+    pub respect_robots: bool,
+    pub robots_agent: String,
+    pub user_agent: String,
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        FetchConfig {
+            timeout: Duration::from_secs(10),
+            max_retries: 3,
+            backoff_base: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            min_host_interval: Duration::from_secs(1),
+            concurrency: 8,
+            per_host_connections: 2,
+            respect_robots: true,
+            robots_agent: String::from("scrapper"),
+            user_agent: String::from(BROWSER_USER_AGENT),
+            cache_dir: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FetchError {
+    InvalidUrl(String),
+    Disallowed(String),
+    Status(StatusCode),
+    Http(reqwest::Error),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::InvalidUrl(url) => write!(f, "invalid url: {}", url),
+            FetchError::Disallowed(url) => write!(f, "disallowed by robots.txt: {}", url),
+            FetchError::Status(status) => write!(f, "server responded with {}", status),
+            FetchError::Http(err) => write!(f, "request failed: {}", err),
+        }
+    }
+}
+
+impl Error for FetchError {}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(err: reqwest::Error) -> Self {
+        FetchError::Http(err)
+    }
+}
+
+// Per-host politeness state shared by every request to that host.
+struct HostState {
+    robots: OnceCell<Robots>,
+    connections: Semaphore,
+    next_slot: Mutex<Instant>,
+}
+
+// The parts of a response the pipeline needs, read while the request permits are held.
+struct Page {
+    status: StatusCode,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: String,
+}
+
+pub struct Fetcher {
+    client: Client,
+    config: FetchConfig,
+    cache: Option<Cache>,
+    requests: Semaphore,
+    hosts: Mutex<HashMap<String, Arc<HostState>>>,
+}
+
+impl Fetcher {
+    pub fn new(config: FetchConfig) -> Result<Self, FetchError> {
+        let mut headers = HeaderMap::new();
+
+        headers.insert(
+            USER_AGENT,
+            HeaderValue::from_str(&config.user_agent)
+                .unwrap_or_else(|_| HeaderValue::from_static(BROWSER_USER_AGENT)),
+        );
+
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static(
+                "text/html,application/xhtml+xml,application/xml;q=0.9,image/webp,*/*;q=0.8",
+            ),
+        );
+
+        let client = Client::builder()
+            .default_headers(headers)
+            .timeout(config.timeout)
+            .pool_max_idle_per_host(config.per_host_connections.max(1))
+            .build()?;
+
+        Ok(Fetcher {
+            client,
+            cache: config.cache_dir.clone().map(Cache::new),
+            requests: Semaphore::new(config.concurrency.max(1)),
+            hosts: Mutex::new(HashMap::new()),
+            config,
+        })
+    }
+
+    pub async fn fetch(&self, url: &str) -> Result<String, FetchError> {
+        let parsed = Url::parse(url).map_err(|_| FetchError::InvalidUrl(url.to_string()))?;
+        let host = host_key(&parsed).ok_or_else(|| FetchError::InvalidUrl(url.to_string()))?;
+        let state = self.host_state(&host);
+
+        if self.config.respect_robots {
+            let robots = state
+                .robots
+                .get_or_init(|| self.fetch_robots(&host, &state))
+                .await;
+            let path = match parsed.query() {
+                Some(query) => format!("{}?{}", parsed.path(), query),
+                None => parsed.path().to_string(),
+            };
+            if !robots.is_allowed(&self.config.robots_agent, &path) {
+                return Err(FetchError::Disallowed(url.to_string()));
+            }
+        }
+
+        let cached = self.cache.as_ref().and_then(|cache| cache.load(url));
+        let page = self
+            .send_with_retries(&state, parsed, cached.as_ref())
+            .await?;
+
+        if page.status == StatusCode::NOT_MODIFIED {
+            return match cached {
+                Some(cached) => Ok(cached.body),
+                None => Err(FetchError::Status(StatusCode::NOT_MODIFIED)),
+            };
+        }
+
+        if let Some(cache) = &self.cache {
+            if page.etag.is_some() || page.last_modified.is_some() {
+                let entry = CachedResponse {
+                    url: url.to_string(),
+                    etag: page.etag,
+                    last_modified: page.last_modified,
+                    body: page.body.clone(),
+                };
+                if let Err(err) = cache.store(&entry) {
+                    eprintln!("Could not cache {}: {}", url, err);
+                }
+            }
+        }
+
+        Ok(page.body)
+    }
+
+    fn host_state(&self, host: &str) -> Arc<HostState> {
+        let mut hosts = self.hosts.lock().unwrap();
+        let state = hosts.entry(host.to_string()).or_insert_with(|| {
+            Arc::new(HostState {
+                robots: OnceCell::new(),
+                connections: Semaphore::new(self.config.per_host_connections.max(1)),
+                next_slot: Mutex::new(Instant::now()),
+            })
+        });
+        Arc::clone(state)
+    }
+
+    async fn fetch_robots(&self, host: &str, state: &HostState) -> Robots {
+        let Ok(robots_url) = Url::parse(&format!("{}/robots.txt", host)) else {
+            return Robots::allow_all();
+        };
+
+        match self.send_with_retries(state, robots_url, None).await {
+            Ok(page) => Robots::parse(&page.body),
+            // A missing robots.txt means there are no restrictions.
+            Err(FetchError::Status(status)) if status.is_client_error() => Robots::allow_all(),
+            // An unreachable robots.txt is treated as a full disallow.
+            Err(err) => {
+                eprintln!("Could not fetch robots.txt for {}: {}", host, err);
+                Robots::disallow_all()
+            }
+        }
+    }
+
+    // Sends a GET, made conditional when a cached copy with validators is available.
+    async fn send_with_retries(
+        &self,
+        state: &HostState,
+        url: Url,
+        cached: Option<&CachedResponse>,
+    ) -> Result<Page, FetchError> {
+        let mut attempt = 0;
+
+        loop {
+            let mut request = self.client.get(url.clone());
+            if let Some(cached) = cached {
+                if let Some(etag) = &cached.etag {
+                    request = request.header(IF_NONE_MATCH, etag);
+                }
+                if let Some(last_modified) = &cached.last_modified {
+                    request = request.header(IF_MODIFIED_SINCE, last_modified);
+                }
+            }
+
+            let result = {
+                // Host permit first, so waiting out the rate limit doesn't hold a global slot.
+                let _connection = state.connections.acquire().await.unwrap();
+                self.wait_for_slot(state).await;
+                let _request = self.requests.acquire().await.unwrap();
+
+                match request.send().await {
+                    Ok(response) => read_page(response).await,
+                    Err(err) => Err(err),
+                }
+            };
+            let can_retry = attempt < self.config.max_retries;
+
+            let delay = match result {
+                Ok((page, _))
+                    if page.status.is_success() || page.status == StatusCode::NOT_MODIFIED =>
+                {
+                    return Ok(page)
+                }
+                Ok((page, retry_after)) if can_retry && is_retryable(page.status) => {
+                    retry_after.unwrap_or_else(|| self.backoff(attempt))
+                }
+                Ok((page, _)) => return Err(FetchError::Status(page.status)),
+                Err(err) if can_retry && (err.is_timeout() || err.is_connect()) => {
+                    self.backoff(attempt)
+                }
+                Err(err) => return Err(FetchError::Http(err)),
+            };
+
+            attempt += 1;
+            tokio::time::sleep(delay.min(self.config.max_backoff)).await;
+        }
+    }
+
+    // Reserves the host's next request slot, spaced by the minimum interval (or its
+    // robots.txt Crawl-delay), and sleeps until it comes up.
+    async fn wait_for_slot(&self, state: &HostState) {
+        let mut interval = self.config.min_host_interval;
+        if self.config.respect_robots {
+            if let Some(delay) = state
+                .robots
+                .get()
+                .and_then(|robots| robots.crawl_delay(&self.config.robots_agent))
+            {
+                interval = interval.max(delay);
+            }
+        }
+
+        let start = {
+            let mut next_slot = state.next_slot.lock().unwrap();
+            let start = (*next_slot).max(Instant::now());
+            *next_slot = start + interval;
+            start
+        };
+
+        tokio::time::sleep_until(start.into()).await;
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.config
+            .backoff_base
+            .saturating_mul(2u32.saturating_pow(attempt))
+    }
+}
+
+fn host_key(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}://{}:{}", url.scheme(), host, port),
+        None => format!("{}://{}", url.scheme(), host),
+    })
+}
+
+async fn read_page(response: Response) -> Result<(Page, Option<Duration>), reqwest::Error> {
+    let status = response.status();
+    let retry_after = retry_after(&response);
+    let etag = header_string(&response, ETAG);
+    let last_modified = header_string(&response, LAST_MODIFIED);
+    let body = response.text().await?;
+
+    let page = Page {
+        status,
+        etag,
+        last_modified,
+        body,
+    };
+    Ok((page, retry_after))
+}
+
+fn header_string(response: &Response, name: reqwest::header::HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    seconds.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    pub struct StandIn {
+        pub base: String,
+        pub hits: Arc<Mutex<Vec<(String, Instant, String)>>>,
+        pub max_in_flight: Arc<AtomicUsize>,
+    }
+
+    impl StandIn {
+        pub fn count(&self, path: &str) -> usize {
+            self.hits
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(hit, _, _)| hit == path)
+                .count()
+        }
+    }
+
+    // (status, extra raw header lines, body)
+    pub type Reply = (u16, &'static str, &'static str);
+
+    // Serves scripted (status, body) responses per path; the last one repeats.
+    pub fn stand_in(routes: Vec<(&'static str, Vec<(u16, &'static str)>)>) -> StandIn {
+        stand_in_with_headers(
+            routes
+                .into_iter()
+                .map(|(path, replies)| {
+                    let replies = replies
+                        .into_iter()
+                        .map(|(status, body)| (status, "", body))
+                        .collect();
+                    (path, replies)
+                })
+                .collect(),
+        )
+    }
+
+    // Like `stand_in`, with extra raw header lines ("Name: value\r\n") per reply.
+    pub fn stand_in_with_headers(routes: Vec<(&'static str, Vec<Reply>)>) -> StandIn {
+        stand_in_with_delay(routes, Duration::ZERO)
+    }
+
+    // Every connection is served on its own thread after `delay`, tracking peak concurrency.
+    pub fn stand_in_with_delay(
+        routes: Vec<(&'static str, Vec<Reply>)>,
+        delay: Duration,
+    ) -> StandIn {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(Mutex::new(Vec::new()));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let routes: Arc<Mutex<HashMap<&'static str, Vec<Reply>>>> =
+            Arc::new(Mutex::new(routes.into_iter().collect()));
+
+        let recorded = Arc::clone(&hits);
+        let peak = Arc::clone(&max_in_flight);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let recorded = Arc::clone(&recorded);
+                let peak = Arc::clone(&peak);
+                let in_flight = Arc::clone(&in_flight);
+                let routes = Arc::clone(&routes);
+
+                thread::spawn(move || {
+                    let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(current, Ordering::SeqCst);
+                    serve(stream, &recorded, &routes, delay);
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        StandIn {
+            base,
+            hits,
+            max_in_flight,
+        }
+    }
+
+    fn serve(
+        mut stream: TcpStream,
+        recorded: &Mutex<Vec<(String, Instant, String)>>,
+        routes: &Mutex<HashMap<&'static str, Vec<Reply>>>,
+        delay: Duration,
+    ) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).is_err() {
+            return;
+        }
+        let mut request_headers = String::new();
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).unwrap_or(0) == 0 || header == "\r\n" {
+                break;
+            }
+            request_headers.push_str(&header.to_ascii_lowercase());
+        }
+
+        let path = request_line
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or("/")
+            .to_string();
+        recorded
+            .lock()
+            .unwrap()
+            .push((path.clone(), Instant::now(), request_headers));
+
+        let (status, headers, body) = match routes.lock().unwrap().get_mut(path.as_str()) {
+            Some(responses) if responses.len() > 1 => responses.remove(0),
+            Some(responses) => responses[0],
+            None => (404, "", "not found"),
+        };
+        thread::sleep(delay);
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {} Stand-In\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        );
+    }
+
+    pub fn test_config() -> FetchConfig {
+        FetchConfig {
+            timeout: Duration::from_secs(2),
+            max_retries: 2,
+            backoff_base: Duration::from_millis(10),
+            min_host_interval: Duration::ZERO,
+            ..FetchConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_until_success() {
+        let server = stand_in(vec![("/page", vec![(503, ""), (429, ""), (200, "ok")])]);
+        let fetcher = Fetcher::new(test_config()).unwrap();
+
+        let body = fetcher
+            .fetch(&format!("{}/page", server.base))
+            .await
+            .unwrap();
+
+        assert_eq!(body, "ok");
+        assert_eq!(server.count("/page"), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let server = stand_in(vec![("/page", vec![(500, "")])]);
+        let fetcher = Fetcher::new(test_config()).unwrap();
+
+        let result = fetcher.fetch(&format!("{}/page", server.base)).await;
+
+        assert!(matches!(
+            result,
+            Err(FetchError::Status(StatusCode::INTERNAL_SERVER_ERROR))
+        ));
+        assert_eq!(server.count("/page"), 3);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let server = stand_in(vec![("/gone", vec![(410, "")])]);
+        let fetcher = Fetcher::new(test_config()).unwrap();
+
+        let result = fetcher.fetch(&format!("{}/gone", server.base)).await;
+
+        assert!(matches!(result, Err(FetchError::Status(StatusCode::GONE))));
+        assert_eq!(server.count("/gone"), 1);
+    }
+
+    #[tokio::test]
+    async fn robots_disallow_blocks_request() {
+        let server = stand_in(vec![
+            (
+                "/robots.txt",
+                vec![(200, "User-agent: *\nDisallow: /private\n")],
+            ),
+            ("/private/page", vec![(200, "secret")]),
+            ("/public", vec![(200, "hello")]),
+        ]);
+        let fetcher = Fetcher::new(test_config()).unwrap();
+
+        let blocked = fetcher
+            .fetch(&format!("{}/private/page", server.base))
+            .await;
+        let allowed = fetcher
+            .fetch(&format!("{}/public", server.base))
+            .await
+            .unwrap();
+
+        assert!(matches!(blocked, Err(FetchError::Disallowed(_))));
+        assert_eq!(allowed, "hello");
+        assert_eq!(server.count("/private/page"), 0);
+        assert_eq!(server.count("/robots.txt"), 1);
+    }
+
+    #[tokio::test]
+    async fn missing_robots_allows_everything() {
+        let server = stand_in(vec![("/page", vec![(200, "ok")])]);
+        let fetcher = Fetcher::new(test_config()).unwrap();
+
+        assert_eq!(
+            fetcher
+                .fetch(&format!("{}/page", server.base))
+                .await
+                .unwrap(),
+            "ok"
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_to_same_host_are_rate_limited() {
+        let server = stand_in(vec![("/a", vec![(200, "a")]), ("/b", vec![(200, "b")])]);
+        let config = FetchConfig {
+            min_host_interval: Duration::from_millis(200),
+            respect_robots: false,
+            ..test_config()
+        };
+        let fetcher = Fetcher::new(config).unwrap();
+
+        fetcher.fetch(&format!("{}/a", server.base)).await.unwrap();
+        fetcher.fetch(&format!("{}/b", server.base)).await.unwrap();
+
+        let hits = server.hits.lock().unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits[1].1.duration_since(hits[0].1) >= Duration::from_millis(190));
+    }
+
+    #[tokio::test]
+    async fn cached_pages_use_conditional_requests() {
+        let server = stand_in_with_headers(vec![(
+            "/page",
+            vec![(200, "ETag: \"v1\"\r\n", "first"), (304, "", "")],
+        )]);
+        let config = FetchConfig {
+            respect_robots: false,
+            cache_dir: Some(crate::cache::tests::temp_dir("conditional")),
+            ..test_config()
+        };
+        let fetcher = Fetcher::new(config).unwrap();
+        let url = format!("{}/page", server.base);
+
+        assert_eq!(fetcher.fetch(&url).await.unwrap(), "first");
+        assert_eq!(fetcher.fetch(&url).await.unwrap(), "first");
+
+        let hits = server.hits.lock().unwrap();
+        assert!(!hits[0].2.contains("if-none-match"));
+        assert!(hits[1].2.contains("if-none-match: \"v1\""));
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+use crate::article::Article;
+
+// Stands in for `link` when the listing entry has none.
+pub const NO_LINK: &str = "No link";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScrapedItem {
+    pub title: String,
+    pub link: String,
+    pub source: String,
+    // Only filled in deep mode; older snapshots without it still load.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub article: Option<Article>,
+}
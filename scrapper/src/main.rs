@@ -1,38 +1,217 @@
-use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
-use scraper::{Html, Selector};
+use scraper::Html;
+use std::env;
 use std::error::Error;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let url = "https://dev.to/ruivalim/";
+mod article;
+mod cache;
+mod diff;
+mod fetch;
+mod item;
+mod parse;
+mod pipeline;
+mod robots;
 
-    let mut headers = HeaderMap::new();
+use fetch::{FetchConfig, Fetcher};
+use item::ScrapedItem;
 
-    headers.insert(USER_AGENT, HeaderValue::from_static(
-        "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36"
-    ));
+const DEFAULT_TARGET: &str = "https://dev.to/ruivalim/";
+const DEFAULT_CACHE_DIR: &str = ".scrapper-cache";
+const SNAPSHOT_FILE: &str = "last_run.json";
 
-    headers.insert(
-        "accept",
-        HeaderValue::from_static(
-            "text/html,application/xhtml+xml,application/xml;q=0.9,image/webp,*/*;q=0.8",
-        ),
-    );
+const USAGE: &str =
+    "Usage: scrapper [--timeout SECS] [--retries N] [--rate-limit MS] [--ignore-robots]
+                [--concurrency N] [--per-host N] [--quiet] [--deep] [--json]
+                [--cache-dir DIR] [--no-cache] [--diff] [--state FILE]
+                [--input FILE|-]... [URL...]";
 
-    let client = reqwest::blocking::Client::builder()
-        .default_headers(headers)
-        .build()?;
+type Loaded = Result<String, Box<dyn Error>>;
 
-    let body = client.get(url).send()?.text()?;
+struct Options {
+    fetch: FetchConfig,
+    targets: Vec<String>,
+    inputs: Vec<String>,
+    diff: bool,
+    state_file: Option<PathBuf>,
+    quiet: bool,
+    deep: bool,
+    json: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let mut options = parse_args(env::args().skip(1))?;
+    if options.targets.is_empty() && options.inputs.is_empty() {
+        options.targets.push(DEFAULT_TARGET.to_string());
+    }
+    let state_file = options.state_file.clone().unwrap_or_else(|| {
+        options
+            .fetch
+            .cache_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CACHE_DIR))
+            .join(SNAPSHOT_FILE)
+    });
+
+    let fetcher = Arc::new(Fetcher::new(options.fetch)?);
 
-    let document = Html::parse_document(&body);
+    // Saved pages are scraped instead of fetching when any --input is given.
+    let (sources, results): (Vec<String>, Vec<Loaded>) = if options.inputs.is_empty() {
+        let show_progress = !options.quiet && options.targets.len() > 1;
+        let results =
+            pipeline::fetch_all(Arc::clone(&fetcher), &options.targets, show_progress).await;
+        (
+            options.targets.clone(),
+            results.into_iter().map(|result| Ok(result?)).collect(),
+        )
+    } else {
+        (
+            options
+                .inputs
+                .iter()
+                .map(|input| input_source(input))
+                .collect(),
+            options
+                .inputs
+                .iter()
+                .map(|input| read_input(input))
+                .collect(),
+        )
+    };
 
-    let selector = Selector::parse(".crayons-story__title a").unwrap();
+    let mut items: Vec<ScrapedItem> = Vec::new();
+    let mut failed: Vec<&String> = Vec::new();
+
+    for (source, result) in sources.iter().zip(results) {
+        let body = match result {
+            Ok(body) => body,
+            Err(err) => {
+                eprintln!("{} - error: {}", source, err);
+                failed.push(source);
+                continue;
+            }
+        };
+
+        items.extend(parse::scrape(&Html::parse_document(&body), source));
+    }
+
+    if options.deep {
+        pipeline::fetch_articles(fetcher, &mut items, !options.quiet).await;
+    }
+
+    if options.diff {
+        let previous = diff::load_snapshot(&state_file)?;
+
+        // Keep the previous items of failed targets so they don't show up as removed.
+        items.extend(
+            previous
+                .iter()
+                .filter(|item| failed.contains(&&item.source))
+                .cloned(),
+        );
+
+        diff::diff(&previous, &items).print();
+        diff::save_snapshot(&state_file, &items)?;
+    } else if options.json {
+        println!("{}", serde_json::to_string_pretty(&items)?);
+    } else {
+        for item in items.iter() {
+            println!("{} - {}", item.title, item.link);
+            if let Some(article) = &item.article {
+                println!(
+                    "    {} | {} min read | tags: {}",
+                    article.published.as_deref().unwrap_or("unknown date"),
+                    article.reading_time_minutes,
+                    article.tags.join(", ")
+                );
+            }
+        }
+    }
 
-    for element in document.select(&selector) {
-        let title = element.text().collect::<Vec<_>>().join("");
-        let link = element.value().attr("href").unwrap_or("No link");
-        println!("{} - {}", title, link);
+    if !failed.is_empty() {
+        eprintln!("{} of {} targets failed", failed.len(), sources.len());
+        if failed.len() == sources.len() {
+            std::process::exit(1);
+        }
     }
 
     Ok(())
 }
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, Box<dyn Error>> {
+    let mut options = Options {
+        fetch: FetchConfig {
+            cache_dir: Some(PathBuf::from(DEFAULT_CACHE_DIR)),
+            ..FetchConfig::default()
+        },
+        targets: Vec::new(),
+        inputs: Vec::new(),
+        diff: false,
+        state_file: None,
+        quiet: false,
+        deep: false,
+        json: false,
+    };
+
+    while let Some(arg) = args.next() {
+        let config = &mut options.fetch;
+        match arg.as_str() {
+            "--timeout" => {
+                config.timeout = Duration::from_secs_f64(next_value(&mut args, &arg)?.parse()?)
+            }
+            "--retries" => config.max_retries = next_value(&mut args, &arg)?.parse()?,
+            "--rate-limit" => {
+                config.min_host_interval =
+                    Duration::from_millis(next_value(&mut args, &arg)?.parse()?)
+            }
+            "--ignore-robots" => config.respect_robots = false,
+            "--concurrency" => config.concurrency = next_value(&mut args, &arg)?.parse()?,
+            "--per-host" => config.per_host_connections = next_value(&mut args, &arg)?.parse()?,
+            "--quiet" => options.quiet = true,
+            "--deep" => options.deep = true,
+            "--json" => options.json = true,
+            "--cache-dir" => config.cache_dir = Some(PathBuf::from(next_value(&mut args, &arg)?)),
+            "--no-cache" => config.cache_dir = None,
+            "--diff" => options.diff = true,
+            "--input" => options.inputs.push(next_value(&mut args, &arg)?),
+            "--state" => options.state_file = Some(PathBuf::from(next_value(&mut args, &arg)?)),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            flag if flag.starts_with("--") => {
+                return Err(format!("unknown option {}\n{}", flag, USAGE).into())
+            }
+            _ => options.targets.push(arg),
+        }
+    }
+
+    Ok(options)
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("missing value for {}\n{}", flag, USAGE))
+}
+
+fn input_source(input: &str) -> String {
+    if input == "-" {
+        String::from("stdin")
+    } else {
+        input.to_string()
+    }
+}
+
+fn read_input(input: &str) -> Loaded {
+    if input == "-" {
+        let mut body = String::new();
+        io::stdin().read_to_string(&mut body)?;
+        Ok(body)
+    } else {
+        Ok(fs::read_to_string(input)?)
+    }
+}
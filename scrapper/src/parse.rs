@@ -0,0 +1,118 @@
+use scraper::{Html, Selector};
+
+use crate::item::{ScrapedItem, NO_LINK};
+
+const TITLE_SELECTOR: &str = ".crayons-story__title a";
+
+// Pure over the parsed document so it can run on live pages, saved files and fixtures alike.
+pub fn scrape(document: &Html, source: &str) -> Vec<ScrapedItem> {
+    let selector = Selector::parse(TITLE_SELECTOR).unwrap();
+
+    document
+        .select(&selector)
+        .map(|element| ScrapedItem {
+            title: collapse_whitespace(&element.text().collect::<Vec<_>>().join("")),
+            link: element
+                .value()
+                .attr("href")
+                .filter(|href| !href.trim().is_empty())
+                .unwrap_or(NO_LINK)
+                .to_string(),
+            source: source.to_string(),
+            article: None,
+        })
+        // The parser re-opens unclosed links in later blocks, leaving empty clones behind.
+        .filter(|item| !item.title.is_empty())
+        .collect()
+}
+
+pub fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scrape_fixture(html: &str) -> Vec<(String, String)> {
+        scrape(&Html::parse_document(html), "fixture")
+            .into_iter()
+            .map(|item| (item.title, item.link))
+            .collect()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(title, link)| (title.to_string(), link.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn scrapes_listing() {
+        let items = scrape_fixture(include_str!("../tests/fixtures/listing.html"));
+
+        assert_eq!(
+            items,
+            pairs(&[
+                ("Learning Rust, part 1", "/ruivalim/learning-rust-1"),
+                (
+                    "Building a game with Bevy",
+                    "https://dev.to/ruivalim/bevy-game"
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn missing_attributes_fall_back() {
+        let items = scrape_fixture(include_str!("../tests/fixtures/missing_attributes.html"));
+
+        assert_eq!(
+            items,
+            pairs(&[
+                ("Draft without a link", "No link"),
+                ("Empty link", "No link"),
+                ("Published", "/ruivalim/published"),
+            ])
+        );
+    }
+
+    #[test]
+    fn nested_text_is_flattened() {
+        let items = scrape_fixture(include_str!("../tests/fixtures/nested_text.html"));
+
+        assert_eq!(
+            items,
+            pairs(&[("🦀 Rust ownership, explained", "/ruivalim/nested")])
+        );
+    }
+
+    #[test]
+    fn malformed_markup_is_recovered() {
+        let items = scrape_fixture(include_str!("../tests/fixtures/malformed.html"));
+
+        assert_eq!(
+            items,
+            pairs(&[
+                ("Unclosed link", "/ruivalim/unclosed"),
+                ("After & broken attrs", "/ruivalim/after"),
+                ("Single <quoted>", "/ruivalim/single-quoted"),
+            ])
+        );
+    }
+
+    #[test]
+    fn empty_document_has_no_items() {
+        assert!(scrape(&Html::parse_document(""), "fixture").is_empty());
+    }
+
+    #[test]
+    fn items_record_their_source() {
+        let document = Html::parse_document(include_str!("../tests/fixtures/listing.html"));
+
+        assert!(scrape(&document, "saved.html")
+            .iter()
+            .all(|item| item.source == "saved.html"));
+    }
+}
@@ -0,0 +1,225 @@
+use reqwest::Url;
+use scraper::Html;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::article;
+use crate::fetch::{FetchError, Fetcher};
+use crate::item::{ScrapedItem, NO_LINK};
+
+// Fetches every url concurrently (bounded by the fetcher's limits) and returns the
+// results in the same order as `urls`, regardless of completion order.
+pub async fn fetch_all(
+    fetcher: Arc<Fetcher>,
+    urls: &[String],
+    show_progress: bool,
+) -> Vec<Result<String, FetchError>> {
+    let total = urls.len();
+    let finished = Arc::new(AtomicUsize::new(0));
+
+    let tasks: Vec<_> = urls
+        .iter()
+        .cloned()
+        .map(|url| {
+            let fetcher = Arc::clone(&fetcher);
+            let finished = Arc::clone(&finished);
+
+            tokio::spawn(async move {
+                let result = fetcher.fetch(&url).await;
+                let done = finished.fetch_add(1, Ordering::SeqCst) + 1;
+                if show_progress {
+                    let status = if result.is_ok() { "ok" } else { "failed" };
+                    eprintln!("[{}/{}] {} {}", done, total, status, url);
+                }
+                result
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(total);
+    for task in tasks {
+        results.push(task.await.expect("fetch task panicked"));
+    }
+    results
+}
+
+// Deep mode: visits every item's link (resolved against the page it was found on)
+// and attaches the extracted article. Failures are reported and leave `article` empty.
+pub async fn fetch_articles(fetcher: Arc<Fetcher>, items: &mut [ScrapedItem], show_progress: bool) {
+    let links: Vec<Option<String>> = items.iter().map(resolve_link).collect();
+    let pending: Vec<String> = links.iter().flatten().cloned().collect();
+    let mut pages = fetch_all(fetcher, &pending, show_progress)
+        .await
+        .into_iter();
+
+    for (item, link) in items.iter_mut().zip(links) {
+        let Some(link) = link else {
+            eprintln!(
+                "{} - skipping article, can't resolve link {}",
+                item.title, item.link
+            );
+            continue;
+        };
+        match pages.next() {
+            Some(Ok(body)) => item.article = Some(article::extract(&Html::parse_document(&body))),
+            Some(Err(err)) => eprintln!("{} - error: {}", link, err),
+            None => {}
+        }
+    }
+}
+
+fn resolve_link(item: &ScrapedItem) -> Option<String> {
+    if item.link == NO_LINK {
+        return None;
+    }
+    if let Ok(url) = Url::parse(&item.link) {
+        return Some(url.to_string());
+    }
+    Url::parse(&item.source)
+        .and_then(|source| source.join(&item.link))
+        .ok()
+        .map(|url| url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch::tests::{stand_in_with_delay, test_config, Reply};
+    use crate::fetch::FetchConfig;
+    use std::time::Duration;
+
+    fn item(link: &str, source: &str) -> ScrapedItem {
+        ScrapedItem {
+            title: String::from("title"),
+            link: link.to_string(),
+            source: source.to_string(),
+            article: None,
+        }
+    }
+
+    fn page_routes() -> Vec<(&'static str, Vec<Reply>)> {
+        vec![
+            ("/1", vec![(200, "", "one")]),
+            ("/2", vec![(200, "", "two")]),
+            ("/3", vec![(200, "", "three")]),
+            ("/4", vec![(200, "", "four")]),
+            ("/5", vec![(200, "", "five")]),
+            ("/6", vec![(200, "", "six")]),
+        ]
+    }
+
+    fn page_urls(base: &str) -> Vec<String> {
+        (1..=6).map(|page| format!("{}/{}", base, page)).collect()
+    }
+
+    #[tokio::test]
+    async fn results_keep_input_order() {
+        let server = stand_in_with_delay(page_routes(), Duration::from_millis(20));
+        let config = FetchConfig {
+            respect_robots: false,
+            per_host_connections: 6,
+            ..test_config()
+        };
+        let fetcher = Arc::new(Fetcher::new(config).unwrap());
+
+        let bodies: Vec<String> = fetch_all(fetcher, &page_urls(&server.base), false)
+            .await
+            .into_iter()
+            .map(|result| result.unwrap())
+            .collect();
+
+        assert_eq!(bodies, vec!["one", "two", "three", "four", "five", "six"]);
+    }
+
+    #[tokio::test]
+    async fn per_host_connections_are_capped() {
+        let server = stand_in_with_delay(page_routes(), Duration::from_millis(100));
+        let config = FetchConfig {
+            respect_robots: false,
+            concurrency: 8,
+            per_host_connections: 2,
+            ..test_config()
+        };
+        let fetcher = Arc::new(Fetcher::new(config).unwrap());
+
+        let results = fetch_all(fetcher, &page_urls(&server.base), false).await;
+
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(server.max_in_flight.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn global_concurrency_is_capped() {
+        let server = stand_in_with_delay(page_routes(), Duration::from_millis(100));
+        let config = FetchConfig {
+            respect_robots: false,
+            concurrency: 3,
+            per_host_connections: 6,
+            ..test_config()
+        };
+        let fetcher = Arc::new(Fetcher::new(config).unwrap());
+
+        let results = fetch_all(fetcher, &page_urls(&server.base), false).await;
+
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(server.max_in_flight.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn links_resolve_against_their_source() {
+        assert_eq!(
+            resolve_link(&item("/ruivalim/post", "https://dev.to/ruivalim/")).as_deref(),
+            Some("https://dev.to/ruivalim/post")
+        );
+        assert_eq!(
+            resolve_link(&item("https://example.com/a", "saved.html")).as_deref(),
+            Some("https://example.com/a")
+        );
+        assert_eq!(resolve_link(&item("/ruivalim/post", "saved.html")), None);
+        assert_eq!(
+            resolve_link(&item(NO_LINK, "https://dev.to/ruivalim/")),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn deep_mode_attaches_articles() {
+        let server = stand_in_with_delay(
+            vec![
+                (
+                    "/post",
+                    vec![(200, "", "<article><p>Hello there</p></article>")],
+                ),
+                ("/missing", vec![(404, "", "")]),
+            ],
+            Duration::ZERO,
+        );
+        let config = FetchConfig {
+            respect_robots: false,
+            ..test_config()
+        };
+        let fetcher = Arc::new(Fetcher::new(config).unwrap());
+        let source = format!("{}/listing", server.base);
+        let mut items = vec![
+            item("/post", &source),
+            item("/missing", &source),
+            item("/post", "saved.html"),
+            item(NO_LINK, &source),
+        ];
+
+        fetch_articles(fetcher, &mut items, false).await;
+
+        assert_eq!(
+            items[0]
+                .article
+                .as_ref()
+                .map(|article| article.text.as_str()),
+            Some("Hello there")
+        );
+        assert_eq!(items[1].article, None);
+        assert_eq!(items[2].article, None);
+        assert_eq!(items[3].article, None);
+        assert_eq!(server.count("/No%20link"), 0);
+        assert_eq!(server.hits.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,226 @@
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Group {
+    agents: Vec<String>,
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Robots {
+    groups: Vec<Group>,
+    disallow_all: bool,
+}
+
+impl Robots {
+    pub fn allow_all() -> Self {
+        Robots::default()
+    }
+
+    pub fn disallow_all() -> Self {
+        Robots {
+            groups: Vec::new(),
+            disallow_all: true,
+        }
+    }
+
+    pub fn parse(body: &str) -> Self {
+        let mut groups: Vec<Group> = Vec::new();
+        let mut current = Group::default();
+        let mut in_rules = false;
+
+        for line in body.lines() {
+            let line = match line.find('#') {
+                Some(index) => &line[..index],
+                None => line,
+            };
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let value = value.trim();
+
+            match key.as_str() {
+                "user-agent" => {
+                    // A user-agent line after rules starts a new group.
+                    if in_rules {
+                        groups.push(std::mem::take(&mut current));
+                        in_rules = false;
+                    }
+                    current.agents.push(value.to_ascii_lowercase());
+                }
+                "allow" | "disallow" => {
+                    in_rules = true;
+                    // An empty Disallow means "allow everything" and adds no rule.
+                    if !value.is_empty() {
+                        current.rules.push(Rule {
+                            allow: key == "allow",
+                            pattern: value.to_string(),
+                        });
+                    }
+                }
+                "crawl-delay" => {
+                    in_rules = true;
+                    if let Ok(seconds) = value.parse::<f64>() {
+                        if seconds.is_finite() && seconds >= 0.0 {
+                            current.crawl_delay = Some(Duration::from_secs_f64(seconds));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if !current.agents.is_empty() {
+            groups.push(current);
+        }
+
+        Robots {
+            groups,
+            disallow_all: false,
+        }
+    }
+
+    pub fn is_allowed(&self, agent: &str, path: &str) -> bool {
+        if self.disallow_all {
+            return false;
+        }
+        let Some(group) = self.group_for(agent) else {
+            return true;
+        };
+
+        // The most specific (longest) matching rule wins, Allow wins ties.
+        let mut best: Option<&Rule> = None;
+        for rule in group.rules.iter() {
+            if !pattern_matches(&rule.pattern, path) {
+                continue;
+            }
+            best = match best {
+                Some(current)
+                    if current.pattern.len() > rule.pattern.len()
+                        || (current.pattern.len() == rule.pattern.len() && current.allow) =>
+                {
+                    Some(current)
+                }
+                _ => Some(rule),
+            };
+        }
+
+        best.map(|rule| rule.allow).unwrap_or(true)
+    }
+
+    pub fn crawl_delay(&self, agent: &str) -> Option<Duration> {
+        self.group_for(agent).and_then(|group| group.crawl_delay)
+    }
+
+    fn group_for(&self, agent: &str) -> Option<&Group> {
+        let agent = agent.to_ascii_lowercase();
+
+        self.groups
+            .iter()
+            .find(|group| {
+                group
+                    .agents
+                    .iter()
+                    .any(|name| name != "*" && agent.contains(name.as_str()))
+            })
+            .or_else(|| {
+                self.groups
+                    .iter()
+                    .find(|group| group.agents.iter().any(|name| name == "*"))
+            })
+    }
+}
+
+// Supports the `*` wildcard and the `$` end anchor from the robots.txt spec.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(stripped) => (stripped, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !path.starts_with(first) {
+        return false;
+    }
+    let mut position = first.len();
+
+    let rest: Vec<&str> = parts.collect();
+    for (index, part) in rest.iter().enumerate() {
+        let is_last = index == rest.len() - 1;
+        if is_last && anchored {
+            return path.len() >= position + part.len() && path.ends_with(part);
+        }
+        match path[position..].find(part) {
+            Some(found) => position += found + part.len(),
+            None => return false,
+        }
+    }
+
+    !anchored || position == path.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "
+# comment line
+User-agent: *
+Disallow: /private
+Allow: /private/public
+Disallow: /*.json$
+Crawl-delay: 2
+
+User-agent: scrapper
+User-agent: other-bot
+Disallow: /
+Allow: /ruivalim
+";
+
+    #[test]
+    fn longest_match_wins() {
+        let robots = Robots::parse(ROBOTS);
+
+        assert!(robots.is_allowed("somebot", "/"));
+        assert!(!robots.is_allowed("somebot", "/private/area"));
+        assert!(robots.is_allowed("somebot", "/private/public/page"));
+    }
+
+    #[test]
+    fn wildcards_and_anchors() {
+        let robots = Robots::parse(ROBOTS);
+
+        assert!(!robots.is_allowed("somebot", "/api/items.json"));
+        assert!(robots.is_allowed("somebot", "/api/items.json?page=2"));
+        assert!(pattern_matches("/a*c*e", "/abcde"));
+        assert!(!pattern_matches("/a*c$", "/abcde"));
+    }
+
+    #[test]
+    fn specific_group_overrides_wildcard() {
+        let robots = Robots::parse(ROBOTS);
+
+        assert!(!robots.is_allowed("Scrapper/0.1", "/"));
+        assert!(robots.is_allowed("Scrapper/0.1", "/ruivalim/"));
+        assert!(!robots.is_allowed("other-bot", "/private"));
+        assert_eq!(robots.crawl_delay("scrapper"), None);
+        assert_eq!(robots.crawl_delay("somebot"), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn empty_and_special_files() {
+        assert!(Robots::parse("").is_allowed("scrapper", "/anything"));
+        assert!(Robots::parse("User-agent: *\nDisallow:\n").is_allowed("scrapper", "/a"));
+        assert!(Robots::allow_all().is_allowed("scrapper", "/a"));
+        assert!(!Robots::disallow_all().is_allowed("scrapper", "/a"));
+    }
+}